@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+
+use image::{ImageBuffer, Luma, Primitive};
+
+use crate::error::{self, Result};
+use crate::img;
+
+const UNPROCESSED: usize = usize::MAX;
+
+fn find_root(parent: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while parent[root] != root {
+        root = parent[root];
+    }
+
+    // Path compression
+    let mut curr = idx;
+    while parent[curr] != root {
+        let next = parent[curr];
+        parent[curr] = root;
+        curr = next;
+    }
+
+    return root;
+}
+
+/// Union-find area filter (Meijster & Wilkinson): pixels are merged into
+/// components from the most extreme level onwards, and a component stops
+/// growing once it meets a component at another level that already covers
+/// `min_area` pixels. Every pixel then takes the level of the root of its
+/// component.
+///
+/// `order` sorts the pixels from the first level to process to the last one.
+fn area_filter<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    min_area: u32,
+    conn: impl Into<img::Neighbourhood>,
+    order: impl Fn(P, P) -> Ordering,
+) -> image::ImageBuffer<Luma<P>, Vec<P>> {
+    let conn = conn.into();
+    let width = img.width();
+    let values = img.as_raw();
+    let min_area = min_area as usize;
+
+    let mut sorted: Vec<usize> = (0..values.len()).collect();
+    sorted.sort_by(|a, b| order(values[*a], values[*b]));
+
+    let mut parent = vec![UNPROCESSED; values.len()];
+    let mut area = vec![0; values.len()];
+
+    for &idx in &sorted {
+        parent[idx] = idx;
+        area[idx] = 1;
+
+        let coords = ((idx as u32) % width, (idx as u32) / width);
+        for ngb_coords in conn.get_neighbours(img, coords) {
+            let ngb_idx = (ngb_coords.1 * width + ngb_coords.0) as usize;
+            if parent[ngb_idx] == UNPROCESSED {
+                continue;
+            }
+
+            let root = find_root(&mut parent, ngb_idx);
+            if root == idx {
+                continue;
+            }
+
+            if values[root] == values[idx] || area[root] < min_area {
+                area[idx] += area[root];
+                parent[root] = idx;
+            } else {
+                // The neighbour is big enough to stay as it is, so this
+                // component cannot merge with anything any more
+                area[idx] = min_area;
+            }
+        }
+    }
+
+    // Parents are processed after their children, so going backwards every
+    // parent already holds its final level
+    let mut result = vec![values[0]; values.len()];
+    for &idx in sorted.iter().rev() {
+        result[idx] = if parent[idx] == idx {
+            values[idx]
+        } else {
+            result[parent[idx]]
+        };
+    }
+
+    return ImageBuffer::from_raw(img.width(), img.height(), result).unwrap();
+}
+
+fn compare<P: Primitive>(a: P, b: P) -> Ordering {
+    return a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+}
+
+/// Removes the bright structures of `img` covering less than `min_area`
+/// pixels: every pixel is lowered to the highest level at which its
+/// connected component of pixels at or above that level covers at least
+/// `min_area` pixels.
+pub fn area_opening<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    min_area: u32,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+    return Ok(area_filter(img, min_area, conn, |a, b| compare(b, a)));
+}
+
+/// Fills the dark structures of `img` covering less than `min_area` pixels,
+/// the dual of `area_opening`.
+pub fn area_closing<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    min_area: u32,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+    return Ok(area_filter(img, min_area, conn, compare));
+}
+
+mod tests {
+
+    #![allow(unused_imports)]
+
+    use std::collections::VecDeque;
+
+    use crate::area::*;
+    use crate::examples::_gen_random_img;
+    use crate::img::{ConnTypes, Neighbourhood};
+
+    /// Area opening by its definition: the highest threshold at which the
+    /// component of the pixel is big enough.
+    fn _brute_area_opening(
+        img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+        min_area: u32,
+        conn: &Neighbourhood,
+    ) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
+        let lowest = img.pixels().map(|p| p.0[0]).min().unwrap();
+
+        return ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            for level in (lowest..=img.get_pixel(x, y).0[0]).rev() {
+                let mut seen = vec![false; (img.width() * img.height()) as usize];
+                let mut queue = VecDeque::from([(x, y)]);
+                seen[(y * img.width() + x) as usize] = true;
+                let mut count = 0;
+
+                while let Some(coords) = queue.pop_front() {
+                    count += 1;
+                    for ngb in conn.get_neighbours(img, coords) {
+                        let idx = (ngb.1 * img.width() + ngb.0) as usize;
+                        if !seen[idx] && img.get_pixel(ngb.0, ngb.1).0[0] >= level {
+                            seen[idx] = true;
+                            queue.push_back(ngb);
+                        }
+                    }
+                }
+
+                if count >= min_area {
+                    return Luma([level]);
+                }
+            }
+
+            Luma([lowest])
+        });
+    }
+
+    #[test]
+    fn test_area_opening_binary() {
+        // Objects of 1, 3 and 5 pixels
+        let rows: [&[u8]; 5] = [
+            &[1, 0, 0, 0, 1, 1],
+            &[0, 0, 1, 0, 1, 1],
+            &[0, 0, 1, 0, 0, 1],
+            &[0, 0, 1, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+        ];
+        let img = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+
+        let mut expected = img.clone();
+        for (x, y) in [(0, 0), (2, 1), (2, 2), (2, 3)] {
+            expected.put_pixel(x, y, Luma([0]));
+        }
+
+        assert_eq!(area_opening(&img, 4, ConnTypes::Four).unwrap(), expected);
+        assert_eq!(area_opening(&img, 1, ConnTypes::Four).unwrap(), img);
+    }
+
+    #[test]
+    fn test_area_opening_matches_definition() {
+        // Small pseudo-random image with values from 0 to 5
+        let img = _gen_random_img(13, 11, 6, 7);
+
+        for conn in [ConnTypes::Four, ConnTypes::Eight] {
+            let conn: Neighbourhood = conn.into();
+            for min_area in [1, 2, 5, 12, 40, 200] {
+                let expected = _brute_area_opening(&img, min_area, &conn);
+                assert_eq!(area_opening(&img, min_area, &conn).unwrap(), expected);
+
+                let mut inv = img.clone();
+                image::imageops::invert(&mut inv);
+                let mut closed = area_closing(&inv, min_area, &conn).unwrap();
+                image::imageops::invert(&mut closed);
+                assert_eq!(closed, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use image::{ImageBuffer, Luma, Primitive};
+use std::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering,
+};
+
+/// A pixel type that can be stored in an atomic cell, so several threads can
+/// update the same image with compare-and-swap operations.
+pub trait AtomicPrimitive: Primitive + Send + Sync {
+    type Atomic: Send + Sync;
+
+    fn new_atomic(value: Self) -> Self::Atomic;
+
+    fn load(atomic: &Self::Atomic) -> Self;
+
+    /// Stores `new` if the cell still holds `current`. On failure, returns the
+    /// value found in the cell.
+    fn compare_exchange(atomic: &Self::Atomic, current: Self, new: Self) -> Result<Self, Self>;
+}
+
+macro_rules! impl_atomic_primitive {
+    ($prim:ty, $atomic:ty) => {
+        impl AtomicPrimitive for $prim {
+            type Atomic = $atomic;
+
+            fn new_atomic(value: Self) -> Self::Atomic {
+                return <$atomic>::new(value);
+            }
+
+            fn load(atomic: &Self::Atomic) -> Self {
+                return atomic.load(Ordering::Acquire);
+            }
+
+            fn compare_exchange(
+                atomic: &Self::Atomic,
+                current: Self,
+                new: Self,
+            ) -> Result<Self, Self> {
+                return atomic.compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire);
+            }
+        }
+    };
+}
+
+impl_atomic_primitive!(u8, AtomicU8);
+impl_atomic_primitive!(u16, AtomicU16);
+impl_atomic_primitive!(u32, AtomicU32);
+impl_atomic_primitive!(u64, AtomicU64);
+impl_atomic_primitive!(i8, AtomicI8);
+impl_atomic_primitive!(i16, AtomicI16);
+impl_atomic_primitive!(i32, AtomicI32);
+impl_atomic_primitive!(i64, AtomicI64);
+
+macro_rules! impl_atomic_float {
+    ($prim:ty, $atomic:ty) => {
+        impl AtomicPrimitive for $prim {
+            type Atomic = $atomic;
+
+            fn new_atomic(value: Self) -> Self::Atomic {
+                return <$atomic>::new(value.to_bits());
+            }
+
+            fn load(atomic: &Self::Atomic) -> Self {
+                return <$prim>::from_bits(atomic.load(Ordering::Acquire));
+            }
+
+            fn compare_exchange(
+                atomic: &Self::Atomic,
+                current: Self,
+                new: Self,
+            ) -> Result<Self, Self> {
+                return atomic
+                    .compare_exchange(
+                        current.to_bits(),
+                        new.to_bits(),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .map(<$prim>::from_bits)
+                    .map_err(<$prim>::from_bits);
+            }
+        }
+    };
+}
+
+impl_atomic_float!(f32, AtomicU32);
+impl_atomic_float!(f64, AtomicU64);
+
+/// A grayscale image whose pixels can be read and updated concurrently.
+pub struct AtomicImg<P: AtomicPrimitive> {
+    width: u32,
+    height: u32,
+    pixels: Vec<P::Atomic>,
+}
+
+impl<P: AtomicPrimitive> AtomicImg<P> {
+    pub fn from_img(img: &ImageBuffer<Luma<P>, Vec<P>>) -> Self {
+        AtomicImg {
+            width: img.width(),
+            height: img.height(),
+            pixels: img.pixels().map(|p| P::new_atomic(p.0[0])).collect(),
+        }
+    }
+
+    /// Copies the current content back into `img`, which must have the same
+    /// dimensions.
+    pub fn write_to(&self, img: &mut ImageBuffer<Luma<P>, Vec<P>>) {
+        for (pixel, atomic) in img.pixels_mut().zip(self.pixels.iter()) {
+            pixel.0[0] = P::load(atomic);
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.height;
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> P {
+        return P::load(&self.pixels[self.index(x, y)]);
+    }
+
+    pub fn compare_exchange(&self, x: u32, y: u32, current: P, new: P) -> Result<P, P> {
+        return P::compare_exchange(&self.pixels[self.index(x, y)], current, new);
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        return (y as usize * self.width as usize) + x as usize;
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use crate::{atomic_img::*, examples::_gen_seq_img};
+
+    #[test]
+    fn test_atomic_img_round_trip() {
+        let img = _gen_seq_img();
+        let atomic = AtomicImg::from_img(&img);
+
+        assert_eq!(atomic.get_pixel(1, 2), 10);
+        assert_eq!(atomic.compare_exchange(1, 2, 10, 42), Ok(10));
+        assert_eq!(atomic.compare_exchange(1, 2, 10, 43), Err(42));
+
+        let mut result = img.clone();
+        atomic.write_to(&mut result);
+
+        let mut expected = img;
+        expected.put_pixel(1, 2, Luma([42]));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_atomic_float() {
+        let atomic = f32::new_atomic(1.5);
+
+        assert_eq!(f32::compare_exchange(&atomic, 1.5, 2.5), Ok(1.5));
+        assert_eq!(f32::load(&atomic), 2.5);
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::{HashSet, VecDeque};
 
 use image::{ImageBuffer, Luma};
 
+use crate::error::{self, Error, Result};
 use crate::{examples::_gen_same_value_image, img, iwp};
 
 const BG: u8 = 0;
@@ -17,6 +18,17 @@ pub enum DistTypes {
 
 type DistFunc = fn(p1: (u32, u32), p2: (u32, u32)) -> u32;
 
+/// Orders candidate background pixels during the propagation. Unlike
+/// `DistFunc` it must be exact, so the Euclidean one is squared instead of
+/// rounded.
+type CmpFunc = fn(p1: (u32, u32), p2: (u32, u32)) -> u64;
+
+fn squared_euclidean_distance(p1: (u32, u32), p2: (u32, u32)) -> u64 {
+    let dx = (p1.0 as i64 - p2.0 as i64).unsigned_abs();
+    let dy = (p1.1 as i64 - p2.1 as i64).unsigned_abs();
+    return dx * dx + dy * dy;
+}
+
 fn aprox_euclidean_distance(p1: (u32, u32), p2: (u32, u32)) -> u32 {
     let exp = ((p1.0 as f64 - p2.0 as f64).powi(2) + (p1.1 as f64 - p2.1 as f64).powi(2)).sqrt();
     return exp.round() as u32;
@@ -36,23 +48,34 @@ fn chessboard_distance(p1: (u32, u32), p2: (u32, u32)) -> u32 {
     return exp as u32;
 }
 
-fn get_one_dimension_coords(
-    img: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
-    coords: (u32, u32),
-) -> u32 {
-    return (coords.1 * img.width()) + coords.0;
+fn get_dist_func(dist_type: DistTypes) -> DistFunc {
+    return match dist_type {
+        DistTypes::Euclidean => aprox_euclidean_distance,
+        DistTypes::Chessboard => chessboard_distance,
+        DistTypes::CityBlock => city_block_distance,
+    };
 }
 
-fn get_two_dimensions_coords(
-    img: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
-    coords: u32,
-) -> (u32, u32) {
-    return (coords % img.width(), coords / img.width());
+fn get_cmp_func(dist_type: DistTypes) -> CmpFunc {
+    return match dist_type {
+        DistTypes::Euclidean => squared_euclidean_distance,
+        DistTypes::Chessboard => |p1, p2| chessboard_distance(p1, p2) as u64,
+        DistTypes::CityBlock => |p1, p2| city_block_distance(p1, p2) as u64,
+    };
+}
+
+fn get_one_dimension_coords(width: u32, coords: (u32, u32)) -> u32 {
+    return (coords.1 * width) + coords.0;
+}
+
+fn get_two_dimensions_coords(width: u32, coords: u32) -> (u32, u32) {
+    return (coords % width, coords / width);
 }
 
 fn get_initial_pixels(
     img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
     vr_diagram: &mut image::ImageBuffer<Luma<u32>, Vec<u32>>,
+    conn: &img::Neighbourhood,
 ) -> VecDeque<(u32, u32)> {
     let width = img.width();
     let height = img.height();
@@ -68,10 +91,9 @@ fn get_initial_pixels(
                 vr_diagram.put_pixel(
                     pixel_coords.0,
                     pixel_coords.1,
-                    Luma([get_one_dimension_coords(vr_diagram, pixel_coords)]),
+                    Luma([get_one_dimension_coords(width, pixel_coords)]),
                 );
-                let pixel_ngbs =
-                    img::get_pixel_neighbours(img, pixel_coords, img::ConnTypes::Eight);
+                let pixel_ngbs = conn.get_neighbours(img, pixel_coords);
 
                 for ngb_coord in pixel_ngbs {
                     let ngb_value = img.get_pixel(ngb_coord.0, ngb_coord.1).0[0];
@@ -92,95 +114,517 @@ fn get_initial_pixels(
     return queue;
 }
 
-fn propagation_condition(
-    img: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
-    curr_pixel: img::PixelT<u32>,
-    ngb_pixel: img::PixelT<u32>,
-    dist_func: &DistFunc,
-) -> bool {
-    let vr_p = get_two_dimensions_coords(img, curr_pixel.value);
-    let vr_q = get_two_dimensions_coords(img, ngb_pixel.value);
-
-    return dist_func(ngb_pixel.coords, vr_p) < dist_func(ngb_pixel.coords, vr_q);
+/// Propagates the Voronoi diagram: every pixel stores the linear index of its
+/// nearest background pixel, and takes its neighbour's one when it is closer.
+struct DTPropagator<F = CmpFunc> {
+    width: u32,
+    cmp_func: F,
 }
 
-fn update_func(
-    _img: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
-    curr_pixel: img::PixelT<u32>,
-    _ngb_pixel: img::PixelT<u32>,
-    _dist_func: &DistFunc,
-) -> u32 {
-    return curr_pixel.value;
+impl<F, D> iwp::Propagator<u32> for DTPropagator<F>
+where
+    F: Fn((u32, u32), (u32, u32)) -> D,
+    D: PartialOrd,
+{
+    fn condition(&self, curr_pixel: img::PixelT<u32>, ngb_pixel: img::PixelT<u32>) -> bool {
+        let vr_p = get_two_dimensions_coords(self.width, curr_pixel.value);
+        let vr_q = get_two_dimensions_coords(self.width, ngb_pixel.value);
+
+        return (self.cmp_func)(ngb_pixel.coords, vr_p) < (self.cmp_func)(ngb_pixel.coords, vr_q);
+    }
+
+    fn update(&self, curr_pixel: img::PixelT<u32>, _ngb_pixel: img::PixelT<u32>) -> u32 {
+        return curr_pixel.value;
+    }
 }
 
-fn get_final_dist_img(
+/// Distance from every pixel to the background pixel the Voronoi diagram
+/// holds for it, or `no_background` when the image has no background pixel.
+fn get_final_dist_img<P: image::Primitive>(
     width: u32,
     height: u32,
-    vr_diagram: &mut image::ImageBuffer<Luma<u32>, Vec<u32>>,
-    dist_func: &DistFunc,
-) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
-    let mut img = _gen_same_value_image(width, height, 0);
+    vr_diagram: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
+    dist_func: impl Fn((u32, u32), (u32, u32)) -> P,
+    no_background: P,
+) -> image::ImageBuffer<Luma<P>, Vec<P>> {
+    let mut img = _gen_same_value_image(width, height, P::zero());
     for i in 0..height {
         for j in 0..width {
             let pixel_coords = (j, i);
-            let vr_p = get_two_dimensions_coords(vr_diagram, vr_diagram.get_pixel(j, i).0[0]);
+            let nearest = vr_diagram.get_pixel(j, i).0[0];
+            if nearest == INF_PIXEL {
+                img.put_pixel(pixel_coords.0, pixel_coords.1, Luma([no_background]));
+                continue;
+            }
+
+            let vr_p = get_two_dimensions_coords(width, nearest);
             let value = dist_func(pixel_coords, vr_p);
 
-            img.put_pixel(pixel_coords.0, pixel_coords.1, Luma([value as u8]));
+            img.put_pixel(pixel_coords.0, pixel_coords.1, Luma([value]));
         }
     }
 
     return img;
 }
 
+/// How `dist_to_u8` fits distances into the `u8` range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistScaling {
+    /// Distances above 255 become 255.
+    Clamp,
+    /// Distances are scaled so that the largest one becomes 255.
+    Normalise,
+}
+
+/// Converts a distance map into a `u8` image, e.g. to display or save it.
+pub fn dist_to_u8(
+    dist: &image::ImageBuffer<Luma<u32>, Vec<u32>>,
+    scaling: DistScaling,
+) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
+    let max_dist = dist.pixels().map(|p| p.0[0]).max().unwrap_or(0) as u64;
+
+    return ImageBuffer::from_fn(dist.width(), dist.height(), |x, y| {
+        let value = dist.get_pixel(x, y).0[0] as u64;
+        let value = match scaling {
+            DistScaling::Clamp => std::cmp::min(value, u8::MAX as u64),
+            DistScaling::Normalise if max_dist == 0 => 0,
+            DistScaling::Normalise => (value * u8::MAX as u64 + max_dist / 2) / max_dist,
+        };
+
+        Luma([value as u8])
+    });
+}
+
+/// The image must be non-empty and binary, holding only `BG` and `FR`
+/// pixels (see `convert_to_binary`).
+fn check_binary(img: &image::ImageBuffer<Luma<u8>, Vec<u8>>) -> Result<()> {
+    error::check_not_empty(img)?;
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if pixel.0[0] != BG && pixel.0[0] != FR {
+            return Err(Error::InvalidValue {
+                coords: (x, y),
+                reason: "the image is not binary",
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+/// Sequential propagation of the Voronoi diagram of the background pixels
+/// of `img`, where `cmp_func` orders the candidate background pixels.
+fn voronoi_diagram<D: PartialOrd>(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    cmp_func: impl Fn((u32, u32), (u32, u32)) -> D,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u32>, Vec<u32>>> {
+    check_binary(img)?;
+
+    let conn = conn.into();
+    let mut vr_diagram = ImageBuffer::new(img.width(), img.height());
+    let mut queue = get_initial_pixels(img, &mut vr_diagram, &conn);
+
+    let propagator = DTPropagator {
+        width: img.width(),
+        cmp_func,
+    };
+
+    iwp::propagate(&mut vr_diagram, &propagator, &mut queue, &conn);
+
+    return Ok(vr_diagram);
+}
+
+/// Distance from every pixel of the binary `img` to its nearest background
+/// pixel, or `u32::MAX` everywhere when the image has no background pixel.
+/// Use `dist_to_u8` to turn the result into a displayable image.
+///
+/// The distances are exact when every pixel has a neighbour in `conn` one
+/// step closer to its nearest background pixel: city-block distances with
+/// the 4- or 8-neighbourhood, chessboard ones with the 8-neighbourhood.
+/// Otherwise, and for Euclidean distances, some pixels may get a slightly
+/// larger distance (see `euclidean_dist_transform` for exact ones).
 pub fn dist_transform(
     img: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
     dist_type: DistTypes,
-) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u32>, Vec<u32>>> {
+    let vr_diagram = voronoi_diagram(img, get_cmp_func(dist_type.clone()), conn)?;
+
+    return Ok(get_final_dist_img(
+        img.width(),
+        img.height(),
+        &vr_diagram,
+        get_dist_func(dist_type),
+        u32::MAX,
+    ));
+}
+
+/// Parallel version of `dist_transform`. The distances are the same as the
+/// ones of `dist_transform` whenever those are exact; otherwise they depend
+/// on the order in which the sections meet and may differ slightly.
+pub fn dist_transform_parallel(
+    img: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    dist_type: DistTypes,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<image::ImageBuffer<Luma<u32>, Vec<u32>>> {
+    check_binary(img)?;
+    error::check_num_threads(num_threads)?;
+
+    let conn = conn.into();
     let mut vr_diagram = ImageBuffer::new(img.width(), img.height());
-    let mut queue = get_initial_pixels(img, &mut vr_diagram);
+    let mut queue = get_initial_pixels(img, &mut vr_diagram, &conn);
 
-    let dist_func = match dist_type {
-        DistTypes::Euclidean => aprox_euclidean_distance,
-        DistTypes::Chessboard => chessboard_distance,
-        DistTypes::CityBlock => city_block_distance,
+    let propagator = DTPropagator {
+        width: img.width(),
+        cmp_func: get_cmp_func(dist_type.clone()),
     };
 
-    iwp::propagate(
-        &mut vr_diagram,
-        propagation_condition,
-        update_func,
-        &mut queue,
-        &dist_func,
-    );
+    iwp::propagate_parallel_exchange(&mut vr_diagram, &propagator, &mut queue, &conn, num_threads)?;
 
-    return get_final_dist_img(img.width(), img.height(), &mut vr_diagram, &dist_func);
+    return Ok(get_final_dist_img(
+        img.width(),
+        img.height(),
+        &vr_diagram,
+        get_dist_func(dist_type),
+        u32::MAX,
+    ));
 }
 
-pub fn dist_transform_parallel(
+/// Nearest background pixel of every pixel, as computed by
+/// `feature_transform`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureMap {
+    width: u32,
+    height: u32,
+    nearest: Vec<(u32, u32)>,
+}
+
+impl FeatureMap {
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.height;
+    }
+
+    /// Coordinates of the background pixel nearest to `(x, y)`.
+    pub fn get(&self, x: u32, y: u32) -> (u32, u32) {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        return self.nearest[get_one_dimension_coords(self.width, (x, y)) as usize];
+    }
+}
+
+/// Nearest background pixel of every pixel of the binary `img` under
+/// `dist_type`, e.g. to assign every pixel to its closest object. Ties are
+/// broken arbitrarily. Fails when the image has no background pixel.
+///
+/// The result is exact for every metric: the Euclidean one is computed
+/// separably, and the others by propagating over the 8-neighbourhood, where
+/// every pixel has a neighbour one step closer to its nearest background
+/// pixel.
+pub fn feature_transform(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    dist_type: DistTypes,
+) -> Result<FeatureMap> {
+    check_binary(img)?;
+    if !img.pixels().any(|p| p.0[0] == BG) {
+        return Err(Error::InvalidArgument {
+            name: "img",
+            reason: "the image has no background pixel",
+        });
+    }
+
+    let nearest = match dist_type {
+        DistTypes::Euclidean => spaced_squared_edt(img, (1.0, 1.0)).1,
+        _ => voronoi_diagram(img, get_cmp_func(dist_type), img::ConnTypes::Eight)?.into_raw(),
+    };
+
+    return Ok(FeatureMap {
+        width: img.width(),
+        height: img.height(),
+        nearest: nearest
+            .into_iter()
+            .map(|idx| get_two_dimensions_coords(img.width(), idx))
+            .collect(),
+    });
+}
+
+/// Exact squared Euclidean distance from every pixel of the binary `img` to
+/// its nearest background pixel, or `u64::MAX` everywhere when the image has
+/// no background pixel.
+pub fn squared_euclidean_dist_transform(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> Result<image::ImageBuffer<Luma<u64>, Vec<u64>>> {
+    check_binary(img)?;
+
+    // Sums of squared integers, so the floats hold them exactly
+    let (dist, _) = spaced_squared_edt(img, (1.0, 1.0));
+    let dist = dist.iter().map(|d| *d as u64).collect();
+    return Ok(ImageBuffer::from_raw(img.width(), img.height(), dist).unwrap());
+}
+
+/// Euclidean distance from every pixel of the binary `img` to its nearest
+/// background pixel, without the rounding of `dist_transform`, or infinity
+/// everywhere when the image has no background pixel.
+pub fn euclidean_dist_transform(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+) -> Result<image::ImageBuffer<Luma<f32>, Vec<f32>>> {
+    check_binary(img)?;
+
+    let (dist, _) = spaced_squared_edt(img, (1.0, 1.0));
+    let dist = dist.iter().map(|d| d.sqrt() as f32).collect();
+    return Ok(ImageBuffer::from_raw(img.width(), img.height(), dist).unwrap());
+}
+
+/// Distance from every foreground pixel of the binary `img` to the nearest
+/// background pixel, and minus the distance from every background pixel to
+/// the nearest foreground pixel, e.g. to initialise a level set. Pixels with
+/// nothing to measure against (when the image holds a single value) get an
+/// infinite distance.
+///
+/// The city-block and chessboard distances come from two IWP propagations,
+/// one over `img` and one over its inverse, so `conn` must give every pixel
+/// a neighbour one step closer to its nearest pixel of the other value (the
+/// 4-neighbourhood for city-block, the 8-neighbourhood for chessboard), as
+/// for `dist_transform`; otherwise some distances are overestimated. The
+/// Euclidean ones skip the propagation and use the exact separable
+/// computation of `euclidean_dist_transform`, so they ignore `conn`.
+pub fn signed_dist_transform(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    dist_type: DistTypes,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<f32>, Vec<f32>>> {
+    check_binary(img)?;
+
+    let conn = conn.into();
+    let inverted = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        Luma([FR - img.get_pixel(x, y).0[0]])
+    });
+
+    let cmp_func = get_cmp_func(dist_type.clone());
+    let dist_map = |binary| match dist_type {
+        DistTypes::Euclidean => euclidean_dist_transform(binary),
+        _ => {
+            let vr_diagram = voronoi_diagram(binary, cmp_func, &conn)?;
+            Ok(get_final_dist_img(
+                img.width(),
+                img.height(),
+                &vr_diagram,
+                |p1, p2| cmp_func(p1, p2) as f32,
+                f32::INFINITY,
+            ))
+        }
+    };
+    let inside = dist_map(img)?;
+    let outside = dist_map(&inverted)?;
+
+    return Ok(ImageBuffer::from_fn(
+        img.width(),
+        img.height(),
+        |x, y| match img.get_pixel(x, y).0[0] {
+            FR => *inside.get_pixel(x, y),
+            _ => Luma([-outside.get_pixel(x, y).0[0]]),
+        },
+    ));
+}
+
+/// Distance between `p1` and `p2` under `dist_type` when pixels are
+/// `spacing.0` wide and `spacing.1` tall. The Euclidean one is squared.
+fn spaced_distance(
+    dist_type: &DistTypes,
+    spacing: (f64, f64),
+    p1: (u32, u32),
+    p2: (u32, u32),
+) -> f64 {
+    let dx = (p1.0 as f64 - p2.0 as f64).abs() * spacing.0;
+    let dy = (p1.1 as f64 - p2.1 as f64).abs() * spacing.1;
+
+    return match dist_type {
+        DistTypes::Euclidean => dx * dx + dy * dy,
+        DistTypes::CityBlock => dx + dy,
+        DistTypes::Chessboard => dx.max(dy),
+    };
+}
+
+/// Exact squared Euclidean distances with pixels `spacing.0` wide and
+/// `spacing.1` tall, computed separably (Felzenszwalb & Huttenlocher): every
+/// column first gets its vertical distances, then every row takes the lower
+/// envelope of the parabolas rooted at its pixels. Also gives the linear
+/// index of the background pixel each distance is measured to, `INF_PIXEL`
+/// when the image has no background pixel.
+///
+/// Propagating the Voronoi diagram between neighbours is not enough for
+/// exact distances: the region of a background pixel is not always
+/// connected through the neighbourhood (often so with the 4-neighbourhood or
+/// on stretched grids), and some pixels never see their nearest one.
+fn spaced_squared_edt(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    spacing: (f64, f64),
+) -> (Vec<f64>, Vec<u32>) {
+    let width = img.width() as usize;
+    let height = img.height() as usize;
+    let mut dist = vec![f64::INFINITY; width * height];
+    let mut nearest = vec![INF_PIXEL; width * height];
+
+    // Row of the nearest background pixel of every pixel in its column
+    let mut nearest_row = vec![0; width * height];
+    for x in 0..width {
+        let mut last_bg = None;
+        for y in 0..height {
+            if img.as_raw()[y * width + x] == BG {
+                last_bg = Some(y);
+            }
+            if let Some(bg) = last_bg {
+                dist[y * width + x] = ((y - bg) as f64 * spacing.1).powi(2);
+                nearest_row[y * width + x] = bg;
+            }
+        }
+
+        last_bg = None;
+        for y in (0..height).rev() {
+            if img.as_raw()[y * width + x] == BG {
+                last_bg = Some(y);
+            }
+            if let Some(bg) = last_bg {
+                let value = ((bg - y) as f64 * spacing.1).powi(2);
+                if value < dist[y * width + x] {
+                    dist[y * width + x] = value;
+                    nearest_row[y * width + x] = bg;
+                }
+            }
+        }
+    }
+
+    let weight = spacing.0 * spacing.0;
+    let mut roots: Vec<usize> = Vec::with_capacity(width);
+    let mut bounds: Vec<f64> = Vec::with_capacity(width);
+    let mut row = vec![0.0; width];
+
+    for y in 0..height {
+        row.copy_from_slice(&dist[y * width..(y + 1) * width]);
+        roots.clear();
+        bounds.clear();
+
+        // Lower envelope of the parabolas, with `bounds[i]` the position
+        // where the parabola of `roots[i]` becomes the lowest one
+        for q in (0..width).filter(|q| row[*q].is_finite()) {
+            loop {
+                let Some(&v) = roots.last() else {
+                    roots.push(q);
+                    bounds.push(f64::NEG_INFINITY);
+                    break;
+                };
+
+                let cross = ((row[q] + weight * (q * q) as f64)
+                    - (row[v] + weight * (v * v) as f64))
+                    / (2.0 * weight * (q - v) as f64);
+                if cross <= *bounds.last().unwrap() {
+                    roots.pop();
+                    bounds.pop();
+                    continue;
+                }
+
+                roots.push(q);
+                bounds.push(cross);
+                break;
+            }
+        }
+
+        if roots.is_empty() {
+            continue;
+        }
+
+        let mut k = 0;
+        for x in 0..width {
+            while k + 1 < roots.len() && bounds[k + 1] < x as f64 {
+                k += 1;
+            }
+
+            let offset = (x as f64 - roots[k] as f64) * spacing.0;
+            dist[y * width + x] = offset * offset + row[roots[k]];
+
+            let bg = (nearest_row[y * width + roots[k]], roots[k]);
+            nearest[y * width + x] = (bg.0 * width + bg.1) as u32;
+        }
+    }
+
+    return (dist, nearest);
+}
+
+/// Same as `dist_transform`, for images whose pixels are `spacing.0` wide
+/// and `spacing.1` tall (e.g. in µm). Distances are given in the same unit
+/// as `spacing`, and are infinite when the image has no background pixel.
+///
+/// `conn` is only used by the city-block and chessboard metrics: the
+/// Euclidean distances are computed exactly, without any neighbourhood.
+pub fn dist_transform_with_spacing(
+    img: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    dist_type: DistTypes,
+    spacing: (f64, f64),
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<f32>, Vec<f32>>> {
+    for value in [spacing.0, spacing.1] {
+        if !(value.is_finite() && value > 0.0) {
+            return Err(Error::InvalidArgument {
+                name: "spacing",
+                reason: "the pixel spacing must be finite and positive",
+            });
+        }
+    }
+
+    if let DistTypes::Euclidean = dist_type {
+        check_binary(img)?;
+
+        let (dist, _) = spaced_squared_edt(img, spacing);
+        let dist = dist.iter().map(|d| d.sqrt() as f32).collect();
+        return Ok(ImageBuffer::from_raw(img.width(), img.height(), dist).unwrap());
+    }
+
+    let cmp_func = |p1, p2| spaced_distance(&dist_type, spacing, p1, p2);
+    let vr_diagram = voronoi_diagram(img, cmp_func, conn)?;
+
+    return Ok(get_final_dist_img(
+        img.width(),
+        img.height(),
+        &vr_diagram,
+        |p1, p2| cmp_func(p1, p2) as f32,
+        f32::INFINITY,
+    ));
+}
+
+/// Same as `dist_transform_parallel`, but every thread propagates over one
+/// shared Voronoi diagram instead of its own section. As there, the
+/// distances only match the ones of `dist_transform` when those are exact.
+pub fn dist_transform_shared(
     img: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
     dist_type: DistTypes,
+    conn: impl Into<img::Neighbourhood>,
     num_threads: u32,
-) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
+) -> Result<image::ImageBuffer<Luma<u32>, Vec<u32>>> {
+    check_binary(img)?;
+    error::check_num_threads(num_threads)?;
+
+    let conn = conn.into();
     let mut vr_diagram = ImageBuffer::new(img.width(), img.height());
-    let mut queue = get_initial_pixels(img, &mut vr_diagram);
+    let mut queue = get_initial_pixels(img, &mut vr_diagram, &conn);
 
-    let dist_func = match dist_type {
-        DistTypes::Euclidean => aprox_euclidean_distance,
-        DistTypes::Chessboard => chessboard_distance,
-        DistTypes::CityBlock => city_block_distance,
+    let propagator = DTPropagator {
+        width: img.width(),
+        cmp_func: get_cmp_func(dist_type.clone()),
     };
 
-    let mut result = iwp::propagate_parallel(
-        &mut vr_diagram,
-        propagation_condition,
-        update_func,
-        &mut queue,
-        &dist_func,
-        num_threads,
-    );
+    iwp::propagate_shared(&mut vr_diagram, &propagator, &mut queue, &conn, num_threads)?;
 
-    return get_final_dist_img(img.width(), img.height(), &mut result, &dist_func);
+    return Ok(get_final_dist_img(
+        img.width(),
+        img.height(),
+        &vr_diagram,
+        get_dist_func(dist_type),
+        u32::MAX,
+    ));
 }
 
 mod tests {
@@ -189,7 +633,10 @@ mod tests {
 
     use crate::{
         dist_transform::*,
-        examples::{_gen_example_img, _gen_same_value_image},
+        error::Error,
+        examples::{
+            _brute_dist_transform, _gen_example_img, _gen_random_binary_img, _gen_same_value_image,
+        },
         format::print_image_by_row,
     };
 
@@ -206,7 +653,7 @@ mod tests {
 
         img.put_pixel(1, 1, Luma([1]));
 
-        let queue = get_initial_pixels(&img, &mut vr_diagram);
+        let queue = get_initial_pixels(&img, &mut vr_diagram, &img::ConnTypes::Eight.into());
         let mut expected: Vec<(u32, u32)> = vec![
             (0, 2),
             (2, 1),
@@ -218,7 +665,11 @@ mod tests {
             (0, 0),
         ];
 
-        assert_eq!(Vec::from_iter(queue).sort(), expected.sort());
+        let mut queue = Vec::from_iter(queue);
+        queue.sort();
+        expected.sort();
+
+        assert_eq!(queue, expected);
 
         let p_value = vr_diagram.get_pixel(1, 1).0[0];
         assert_eq!(p_value, INF_PIXEL);
@@ -290,7 +741,8 @@ mod tests {
         let mut img = _gen_same_value_image(3, 3, 1);
         img.put_pixel(2, 2, Luma([0]));
 
-        let dis_img = dist_transform(&mut img, DistTypes::Euclidean);
+        let dis_img =
+            dist_transform(&mut img, DistTypes::Euclidean, img::ConnTypes::Eight).unwrap();
 
         let mut expected = _gen_same_value_image(3, 3, 2);
         expected.put_pixel(0, 0, Luma([3]));
@@ -307,7 +759,8 @@ mod tests {
         let mut img = _gen_same_value_image(3, 3, 1);
         img.put_pixel(2, 2, Luma([0]));
 
-        let dis_img = dist_transform(&mut img, DistTypes::CityBlock);
+        let dis_img =
+            dist_transform(&mut img, DistTypes::CityBlock, img::ConnTypes::Eight).unwrap();
 
         let mut expected = _gen_same_value_image(3, 3, 2);
         expected.put_pixel(0, 0, Luma([4]));
@@ -325,7 +778,8 @@ mod tests {
         let mut img = _gen_same_value_image(3, 3, 1);
         img.put_pixel(2, 2, Luma([0]));
 
-        let dis_img = dist_transform(&mut img, DistTypes::Chessboard);
+        let dis_img =
+            dist_transform(&mut img, DistTypes::Chessboard, img::ConnTypes::Eight).unwrap();
 
         let mut expected = _gen_same_value_image(3, 3, 2);
         expected.put_pixel(1, 1, Luma([1]));
@@ -335,4 +789,430 @@ mod tests {
 
         assert_eq!(dis_img, expected);
     }
+
+    #[test]
+    fn test_city_block_dist_transform_four_conn() {
+        let mut img = _gen_same_value_image(3, 3, 1);
+        img.put_pixel(2, 2, Luma([0]));
+
+        let dis_img = dist_transform(&mut img, DistTypes::CityBlock, img::ConnTypes::Four).unwrap();
+
+        let mut expected = _gen_same_value_image(3, 3, 2);
+        expected.put_pixel(0, 0, Luma([4]));
+        expected.put_pixel(0, 1, Luma([3]));
+        expected.put_pixel(1, 0, Luma([3]));
+        expected.put_pixel(1, 2, Luma([1]));
+        expected.put_pixel(2, 1, Luma([1]));
+        expected.put_pixel(2, 2, Luma([0]));
+
+        assert_eq!(dis_img, expected);
+    }
+
+    #[test]
+    fn test_dist_transform_parallel_and_shared() {
+        let mut img = _gen_same_value_image(40, 30, 1);
+        for (x, y) in [(0, 0), (17, 3), (5, 22), (33, 14), (39, 29)] {
+            img.put_pixel(x, y, Luma([0]));
+        }
+
+        for dist_type in [
+            DistTypes::Euclidean,
+            DistTypes::CityBlock,
+            DistTypes::Chessboard,
+        ] {
+            let expected =
+                dist_transform(&mut img, dist_type.clone(), img::ConnTypes::Eight).unwrap();
+            let result =
+                dist_transform_parallel(&mut img, dist_type.clone(), img::ConnTypes::Eight, 6)
+                    .unwrap();
+            assert_eq!(expected, result);
+
+            let result =
+                dist_transform_shared(&mut img, dist_type, img::ConnTypes::Eight, 4).unwrap();
+            assert_eq!(expected, result);
+        }
+
+        // Metrics and neighbourhoods where every pixel has a neighbour one
+        // step closer to its nearest background pixel, so the distances do
+        // not depend on the order pixels are processed in
+        let exact = [
+            (DistTypes::CityBlock, img::ConnTypes::Four),
+            (DistTypes::CityBlock, img::ConnTypes::Eight),
+            (DistTypes::Chessboard, img::ConnTypes::Eight),
+        ];
+        for seed in 0..20 {
+            let mut img = _gen_random_binary_img(60, 45, 40, seed);
+            if !img.pixels().any(|p| p.0[0] == BG) {
+                continue;
+            }
+
+            for (dist_type, conn) in exact.clone() {
+                let dist_func = get_dist_func(dist_type.clone());
+                let brute = _brute_dist_transform(&img, |p1, p2| dist_func(p1, p2) as f64);
+                let expected = dist_transform(&mut img, dist_type.clone(), conn).unwrap();
+                assert!(expected
+                    .pixels()
+                    .zip(brute.pixels())
+                    .all(|(d, b)| d.0[0] as f64 == b.0[0]));
+
+                let result = dist_transform_parallel(&mut img, dist_type.clone(), conn, 4).unwrap();
+                assert_eq!(expected, result);
+
+                let result = dist_transform_shared(&mut img, dist_type, conn, 3).unwrap();
+                assert_eq!(expected, result);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_euclidean_dist_transform() {
+        for (width, height, step) in [(23, 17, 3), (40, 31, 7), (64, 48, 29)] {
+            // Pseudo-random background pixels, denser in the smaller images
+            let img = _gen_random_binary_img(width, height, step, width * height);
+            let expected =
+                _brute_dist_transform(&img, |p1, p2| squared_euclidean_distance(p1, p2) as f64);
+
+            let squared = squared_euclidean_dist_transform(&img).unwrap();
+            assert!(squared
+                .pixels()
+                .zip(expected.pixels())
+                .all(|(a, b)| a.0[0] as f64 == b.0[0]));
+
+            let dist = euclidean_dist_transform(&img).unwrap();
+            for (x, y, pixel) in dist.enumerate_pixels() {
+                let exact = expected.get_pixel(x, y).0[0].sqrt() as f32;
+                assert!((pixel.0[0] - exact).abs() < 1e-5);
+            }
+
+            // The 4-neighbourhood misses some nearest background pixels when
+            // propagating, which must not show here
+            for conn in [img::ConnTypes::Four, img::ConnTypes::Eight] {
+                let signed = signed_dist_transform(&img, DistTypes::Euclidean, conn).unwrap();
+                for (x, y, pixel) in signed.enumerate_pixels() {
+                    if img.get_pixel(x, y).0[0] == FR {
+                        let exact = expected.get_pixel(x, y).0[0].sqrt() as f32;
+                        assert!((pixel.0[0] - exact).abs() < 1e-5);
+                    }
+                }
+            }
+        }
+
+        let no_background = _gen_same_value_image(3, 2, 1u8);
+        assert_eq!(
+            squared_euclidean_dist_transform(&no_background).unwrap(),
+            _gen_same_value_image(3, 2, u64::MAX)
+        );
+        assert!(euclidean_dist_transform(&no_background)
+            .unwrap()
+            .pixels()
+            .all(|p| p.0[0] == f32::INFINITY));
+    }
+
+    #[test]
+    fn test_feature_transform() {
+        let mut img = _gen_same_value_image(9, 7, 1);
+        let seeds = [(0, 0), (8, 1), (4, 6)];
+        for (x, y) in seeds {
+            img.put_pixel(x, y, Luma([0]));
+        }
+
+        for dist_type in [
+            DistTypes::Euclidean,
+            DistTypes::CityBlock,
+            DistTypes::Chessboard,
+        ] {
+            let cmp_func = get_cmp_func(dist_type.clone());
+            let features = feature_transform(&img, dist_type).unwrap();
+            assert_eq!((features.width(), features.height()), (9, 7));
+
+            for (x, y, _) in img.enumerate_pixels() {
+                let nearest = features.get(x, y);
+                assert!(seeds.contains(&nearest));
+
+                let min_dist = seeds.iter().map(|s| cmp_func((x, y), *s)).min();
+                assert_eq!(Some(cmp_func((x, y), nearest)), min_dist);
+            }
+            for seed in seeds {
+                assert_eq!(features.get(seed.0, seed.1), seed);
+            }
+        }
+
+        assert_eq!(
+            feature_transform(&_gen_same_value_image(4, 3, 1u8), DistTypes::Euclidean),
+            Err(Error::InvalidArgument {
+                name: "img",
+                reason: "the image has no background pixel"
+            })
+        );
+    }
+
+    #[test]
+    fn test_feature_transform_matches_brute_force() {
+        for seed in 0..30 {
+            let img = _gen_random_binary_img(24, 20, 9, seed);
+            if !img.pixels().any(|p| p.0[0] == BG) {
+                continue;
+            }
+
+            for dist_type in [
+                DistTypes::Euclidean,
+                DistTypes::CityBlock,
+                DistTypes::Chessboard,
+            ] {
+                let cmp_func = get_cmp_func(dist_type.clone());
+                let expected = _brute_dist_transform(&img, |p1, p2| cmp_func(p1, p2) as f64);
+                let features = feature_transform(&img, dist_type).unwrap();
+
+                for (x, y, dist) in expected.enumerate_pixels() {
+                    let nearest = features.get(x, y);
+                    assert_eq!(img.get_pixel(nearest.0, nearest.1).0[0], BG);
+                    assert_eq!(cmp_func((x, y), nearest) as f64, dist.0[0]);
+                }
+            }
+        }
+
+        // Only one background pixel, in the middle of a single row
+        let mut img = _gen_same_value_image(5, 1, FR);
+        img.put_pixel(2, 0, Luma([BG]));
+        let features = feature_transform(&img, DistTypes::Chessboard).unwrap();
+        for x in 0..5 {
+            assert_eq!(features.get(x, 0), (2, 0));
+        }
+    }
+
+    #[test]
+    fn test_dist_transform_with_spacing() {
+        let img = _gen_random_binary_img(31, 23, 17, 11);
+
+        for (dist_type, exact, spacing) in [
+            (
+                DistTypes::Euclidean,
+                (|dx, dy| f64::hypot(dx, dy)) as fn(f64, f64) -> f64,
+                (0.5, 1.2),
+            ),
+            (
+                DistTypes::Euclidean,
+                |dx, dy| f64::hypot(dx, dy),
+                (0.2, 3.0),
+            ),
+            (DistTypes::CityBlock, |dx, dy| dx + dy, (0.5, 1.2)),
+            (DistTypes::Chessboard, |dx, dy| dx.max(dy), (0.5, 1.2)),
+        ] {
+            let dist = dist_transform_with_spacing(
+                &img,
+                dist_type.clone(),
+                spacing,
+                img::ConnTypes::Eight,
+            )
+            .unwrap();
+            let expected = _brute_dist_transform(&img, |p1, p2| {
+                let dx = (p1.0 as f64 - p2.0 as f64).abs() * spacing.0;
+                let dy = (p1.1 as f64 - p2.1 as f64).abs() * spacing.1;
+                exact(dx, dy)
+            });
+
+            for (pixel, expected) in dist.pixels().zip(expected.pixels()) {
+                assert!((pixel.0[0] as f64 - expected.0[0]).abs() < 1e-4);
+            }
+
+            let no_background = dist_transform_with_spacing(
+                &_gen_same_value_image(3, 2, 1u8),
+                dist_type,
+                spacing,
+                img::ConnTypes::Eight,
+            )
+            .unwrap();
+            assert!(no_background.pixels().all(|p| p.0[0] == f32::INFINITY));
+        }
+
+        // Square pixels of size 1 give back the unscaled distances
+        let dist = dist_transform_with_spacing(
+            &img,
+            DistTypes::CityBlock,
+            (1.0, 1.0),
+            img::ConnTypes::Eight,
+        )
+        .unwrap();
+        let expected = dist_transform(
+            &mut img.clone(),
+            DistTypes::CityBlock,
+            img::ConnTypes::Eight,
+        )
+        .unwrap();
+        assert!(dist
+            .pixels()
+            .zip(expected.pixels())
+            .all(|(a, b)| a.0[0] == b.0[0] as f32));
+
+        assert_eq!(
+            dist_transform_with_spacing(
+                &img,
+                DistTypes::Euclidean,
+                (0.0, 1.0),
+                img::ConnTypes::Eight
+            ),
+            Err(Error::InvalidArgument {
+                name: "spacing",
+                reason: "the pixel spacing must be finite and positive"
+            })
+        );
+    }
+
+    #[test]
+    fn test_signed_dist_transform() {
+        let rows: [&[u8]; 5] = [
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 1, 1, 1, 0, 0],
+            &[0, 1, 1, 1, 0, 0],
+            &[0, 1, 1, 1, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+        ];
+        let img = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+
+        let signed =
+            signed_dist_transform(&img, DistTypes::CityBlock, img::ConnTypes::Eight).unwrap();
+        assert_eq!(signed.get_pixel(2, 2).0[0], 2.0);
+        assert_eq!(signed.get_pixel(1, 1).0[0], 1.0);
+        assert_eq!(signed.get_pixel(0, 0).0[0], -2.0);
+        assert_eq!(signed.get_pixel(5, 2).0[0], -2.0);
+        assert_eq!(signed.get_pixel(4, 2).0[0], -1.0);
+
+        let signed =
+            signed_dist_transform(&img, DistTypes::Euclidean, img::ConnTypes::Eight).unwrap();
+        let mut inverted = img.clone();
+        inverted.pixels_mut().for_each(|p| p.0[0] = 1 - p.0[0]);
+        let inside = euclidean_dist_transform(&img).unwrap();
+        let outside = euclidean_dist_transform(&inverted).unwrap();
+        for (x, y, pixel) in signed.enumerate_pixels() {
+            let expected = inside.get_pixel(x, y).0[0] - outside.get_pixel(x, y).0[0];
+            assert_eq!(pixel.0[0], expected);
+        }
+
+        let flat = signed_dist_transform(
+            &_gen_same_value_image(3, 2, 0u8),
+            DistTypes::Euclidean,
+            img::ConnTypes::Eight,
+        )
+        .unwrap();
+        assert!(flat.pixels().all(|p| p.0[0] == f32::NEG_INFINITY));
+
+        for seed in 0..10 {
+            let img = _gen_random_binary_img(48, 40, 3, seed);
+            let mut inverted = img.clone();
+            inverted.pixels_mut().for_each(|p| p.0[0] = 1 - p.0[0]);
+
+            for (dist_type, conn) in [
+                (DistTypes::Euclidean, img::ConnTypes::Four),
+                (DistTypes::CityBlock, img::ConnTypes::Four),
+                (DistTypes::Chessboard, img::ConnTypes::Eight),
+            ] {
+                let cmp_func = get_cmp_func(dist_type.clone());
+                let dist = |p1, p2| match dist_type {
+                    DistTypes::Euclidean => (cmp_func(p1, p2) as f64).sqrt(),
+                    _ => cmp_func(p1, p2) as f64,
+                };
+                let inside = _brute_dist_transform(&img, dist);
+                let outside = _brute_dist_transform(&inverted, dist);
+
+                let signed = signed_dist_transform(&img, dist_type.clone(), conn).unwrap();
+                for (x, y, pixel) in signed.enumerate_pixels() {
+                    let expected = match img.get_pixel(x, y).0[0] {
+                        FR => inside.get_pixel(x, y).0[0],
+                        _ => -outside.get_pixel(x, y).0[0],
+                    };
+                    assert_eq!(pixel.0[0], expected as f32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dist_transform_full_range() {
+        let mut img = _gen_same_value_image(600, 2, 1);
+        img.put_pixel(0, 0, Luma([0]));
+
+        for dist_type in [
+            DistTypes::Euclidean,
+            DistTypes::CityBlock,
+            DistTypes::Chessboard,
+        ] {
+            let dist = dist_transform(&mut img, dist_type, img::ConnTypes::Eight).unwrap();
+            assert_eq!(dist.get_pixel(0, 0).0[0], 0);
+            assert_eq!(dist.get_pixel(300, 0).0[0], 300);
+            assert_eq!(dist.get_pixel(599, 0).0[0], 599);
+        }
+    }
+
+    #[test]
+    fn test_dist_transform_without_background() {
+        let mut img = _gen_same_value_image(4, 3, 1u8);
+        let expected = _gen_same_value_image(4, 3, u32::MAX);
+
+        for dist_type in [
+            DistTypes::Euclidean,
+            DistTypes::CityBlock,
+            DistTypes::Chessboard,
+        ] {
+            let dist = dist_transform(&mut img, dist_type.clone(), img::ConnTypes::Eight).unwrap();
+            assert_eq!(dist, expected);
+
+            let dist =
+                dist_transform_parallel(&mut img, dist_type.clone(), img::ConnTypes::Eight, 2)
+                    .unwrap();
+            assert_eq!(dist, expected);
+
+            let dist =
+                dist_transform_shared(&mut img, dist_type, img::ConnTypes::Eight, 2).unwrap();
+            assert_eq!(dist, expected);
+        }
+    }
+
+    #[test]
+    fn test_dist_to_u8() {
+        let dist = ImageBuffer::from_raw(4, 1, vec![0u32, 100, 255, 510]).unwrap();
+
+        assert_eq!(
+            dist_to_u8(&dist, DistScaling::Clamp).into_raw(),
+            vec![0, 100, 255, 255]
+        );
+        assert_eq!(
+            dist_to_u8(&dist, DistScaling::Normalise).into_raw(),
+            vec![0, 50, 128, 255]
+        );
+        assert_eq!(
+            dist_to_u8(&_gen_same_value_image(2, 2, 0u32), DistScaling::Normalise).into_raw(),
+            vec![0; 4]
+        );
+    }
+
+    #[test]
+    fn test_dist_transform_rejects_bad_input() {
+        let mut img = _gen_same_value_image(3, 3, 1u8);
+        img.put_pixel(2, 1, image::Luma([255]));
+
+        assert_eq!(
+            dist_transform(&mut img, DistTypes::Euclidean, img::ConnTypes::Eight),
+            Err(Error::InvalidValue {
+                coords: (2, 1),
+                reason: "the image is not binary"
+            })
+        );
+        assert_eq!(
+            dist_transform(
+                &mut _gen_same_value_image(3, 0, 1u8),
+                DistTypes::Euclidean,
+                img::ConnTypes::Eight
+            ),
+            Err(Error::EmptyImage)
+        );
+        assert_eq!(
+            dist_transform_parallel(
+                &mut _gen_same_value_image(3, 3, 1u8),
+                DistTypes::Euclidean,
+                img::ConnTypes::Eight,
+                0
+            ),
+            Err(Error::NoThreads)
+        );
+    }
 }
@@ -0,0 +1,134 @@
+use crate::monitor::Cancelled;
+use image::{Luma, Primitive};
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong when calling the crate's public functions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Two images that must have the same dimensions do not.
+    DimensionMismatch {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    /// The image has no pixels.
+    EmptyImage,
+    /// A parallel function was asked to run on zero threads or sections.
+    NoThreads,
+    /// The image is too small to be split into that many sections.
+    TooManySections {
+        num_sections: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A pixel holds a value the function does not accept.
+    InvalidValue {
+        coords: (u32, u32),
+        reason: &'static str,
+    },
+    /// A parameter is out of the range the function accepts.
+    InvalidArgument {
+        name: &'static str,
+        reason: &'static str,
+    },
+    /// The propagation was stopped through its `CancelToken`.
+    Cancelled(Cancelled),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Error::DimensionMismatch { expected, found } => write!(
+                f,
+                "expected an image of {}x{} pixels, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            Error::EmptyImage => write!(f, "the image has no pixels"),
+            Error::NoThreads => write!(f, "the number of threads or sections must be at least 1"),
+            Error::TooManySections {
+                num_sections,
+                width,
+                height,
+            } => write!(
+                f,
+                "a {}x{} image cannot be split into {} sections",
+                width, height, num_sections
+            ),
+            Error::InvalidValue { coords, reason } => {
+                write!(f, "invalid pixel at {:?}: {}", coords, reason)
+            }
+            Error::InvalidArgument { name, reason } => {
+                write!(f, "invalid argument `{}`: {}", name, reason)
+            }
+            Error::Cancelled(cancelled) => cancelled.fmt(f),
+        };
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Cancelled> for Error {
+    fn from(cancelled: Cancelled) -> Self {
+        return Error::Cancelled(cancelled);
+    }
+}
+
+pub(crate) fn check_not_empty<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+) -> Result<()> {
+    if img.width() == 0 || img.height() == 0 {
+        return Err(Error::EmptyImage);
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn check_same_dimensions<P: Primitive, Q: Primitive>(
+    expected: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    found: &image::ImageBuffer<Luma<Q>, Vec<Q>>,
+) -> Result<()> {
+    if expected.dimensions() != found.dimensions() {
+        return Err(Error::DimensionMismatch {
+            expected: expected.dimensions(),
+            found: found.dimensions(),
+        });
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn check_num_threads(num_threads: u32) -> Result<()> {
+    if num_threads == 0 {
+        return Err(Error::NoThreads);
+    }
+
+    return Ok(());
+}
+
+mod tests {
+
+    #![allow(unused_imports)]
+
+    use crate::error::*;
+    use crate::examples::_gen_same_value_image;
+
+    #[test]
+    fn checks() {
+        let img = _gen_same_value_image(3, 2, 0);
+
+        assert_eq!(check_not_empty(&img), Ok(()));
+        assert_eq!(
+            check_not_empty(&_gen_same_value_image(0, 2, 0)),
+            Err(Error::EmptyImage)
+        );
+        assert_eq!(
+            check_same_dimensions(&img, &_gen_same_value_image(2, 3, 0)),
+            Err(Error::DimensionMismatch {
+                expected: (3, 2),
+                found: (2, 3)
+            })
+        );
+        assert_eq!(check_num_threads(0), Err(Error::NoThreads));
+    }
+}
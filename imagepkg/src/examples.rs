@@ -176,3 +176,71 @@ pub fn _gen_seq_img() -> ImageBuffer<Luma<u8>, Vec<u8>> {
 
     return base_img;
 }
+
+/// Loads the mask and marker images of `tests/imgs/mr`, in that order.
+#[allow(clippy::type_complexity)]
+pub fn _load_mr_imgs() -> (
+    ImageBuffer<Luma<u8>, Vec<u8>>,
+    ImageBuffer<Luma<u8>, Vec<u8>>,
+) {
+    let load = |path| {
+        image::io::Reader::open(path)
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_luma8()
+    };
+
+    return (
+        load("./tests/imgs/mr/mask.png"),
+        load("./tests/imgs/mr/marker.png"),
+    );
+}
+
+/// Gens a `width` x `height` image of pseudo-random values in `0..levels`,
+/// always the same for a given `seed`.
+pub fn _gen_random_img(
+    width: u32,
+    height: u32,
+    levels: u32,
+    seed: u32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let mut state = seed;
+    return ImageBuffer::from_fn(width, height, |_, _| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        Luma([((state >> 16) % levels) as u8])
+    });
+}
+
+/// Gens a binary image where about one pixel in `background_one_in` is
+/// background (0) and the others foreground (1).
+pub fn _gen_random_binary_img(
+    width: u32,
+    height: u32,
+    background_one_in: u32,
+    seed: u32,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let mut img = _gen_random_img(width, height, background_one_in, seed);
+    img.pixels_mut()
+        .for_each(|p| p.0[0] = std::cmp::min(p.0[0], 1));
+    return img;
+}
+
+/// Distance from every pixel of the binary `img` to its nearest background
+/// pixel under `dist`, checking every background pixel in turn. Infinite
+/// when the image has no background pixel.
+pub fn _brute_dist_transform(
+    img: &ImageBuffer<Luma<u8>, Vec<u8>>,
+    dist: impl Fn((u32, u32), (u32, u32)) -> f64,
+) -> ImageBuffer<Luma<f64>, Vec<f64>> {
+    let background: Vec<(u32, u32)> = img
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p.0[0] == 0)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+
+    return ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let nearest = background.iter().map(|b| dist((x, y), *b));
+        Luma([nearest.fold(f64::INFINITY, f64::min)])
+    });
+}
@@ -8,7 +8,7 @@ pub fn print_image_by_row<P: Primitive + std::fmt::Debug>(
         for pixel in row {
             print!("{:?} ", pixel.0[0]);
         }
-        print!("]\n");
+        println!("]");
     }
     println!();
 }
@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use image::{Luma, Primitive};
 
+use crate::error::{self, Result};
 use crate::{examples::_gen_same_value_image, parallel_img::ParallelSection};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -10,43 +11,175 @@ pub struct PixelT<P: Primitive> {
     pub value: P,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ConnTypes {
     Four = 4,
     Eight = 8,
 }
 
-pub fn get_pixel_neighbours<P: Primitive>(
-    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-    coords: (u32, u32),
-    conn: ConnTypes,
-) -> Vec<(u32, u32)> {
-    let x = coords.0;
-    let y = coords.1;
-    let mut neighbours = Vec::new();
-
-    let floor_x = if x != 0 { x - 1 } else { x };
-    let floor_y = if y != 0 { y - 1 } else { y };
-
-    for i in (floor_x)..(x + 2) {
-        for j in (floor_y)..(y + 2) {
-            if !(i == x && j == y) {
-                match img.get_pixel_checked(i, j) {
-                    Some(_) => match conn {
-                        ConnTypes::Four => {
-                            if i == x || j == y {
-                                neighbours.push((i, j));
-                            }
-                        }
-
-                        ConnTypes::Eight => neighbours.push((i, j)),
-                    },
-                    None => continue,
+/// An arbitrary set of `(dx, dy)` offsets describing which pixels are
+/// neighbours of a given pixel (a structuring element).
+///
+/// `ConnTypes::Four` and `ConnTypes::Eight` convert into the usual 3x3
+/// neighbourhoods, so every API taking `impl Into<Neighbourhood>` accepts
+/// them directly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Neighbourhood {
+    offsets: Vec<(i32, i32)>,
+}
+
+impl Neighbourhood {
+    /// Builds a neighbourhood from raw offsets. The origin and repeated
+    /// offsets are ignored, the order of the remaining ones is kept.
+    pub fn new(offsets: Vec<(i32, i32)>) -> Self {
+        let mut unique = Vec::new();
+        for offset in offsets {
+            if offset != (0, 0) && !unique.contains(&offset) {
+                unique.push(offset);
+            }
+        }
+
+        return Neighbourhood { offsets: unique };
+    }
+
+    /// Every offset within euclidean distance `radius` of the origin.
+    pub fn disc(radius: u32) -> Self {
+        let r = radius as i32;
+        let mut offsets = Vec::new();
+        for dx in -r..=r {
+            for dy in -r..=r {
+                if dx * dx + dy * dy <= r * r {
+                    offsets.push((dx, dy));
                 }
             }
         }
+
+        return Neighbourhood::new(offsets);
+    }
+
+    /// Every offset within chessboard distance `radius` of the origin.
+    pub fn square(radius: u32) -> Self {
+        let r = radius as i32;
+        let mut offsets = Vec::new();
+        for dx in -r..=r {
+            for dy in -r..=r {
+                offsets.push((dx, dy));
+            }
+        }
+
+        return Neighbourhood::new(offsets);
+    }
+
+    /// The eight moves of a chess knight.
+    pub fn knight() -> Self {
+        return Neighbourhood::new(vec![
+            (-2, -1),
+            (-2, 1),
+            (-1, -2),
+            (-1, 2),
+            (1, -2),
+            (1, 2),
+            (2, -1),
+            (2, 1),
+        ]);
+    }
+
+    /// The offsets already visited when the pixel is reached by a raster
+    /// scan going left to right, top to bottom.
+    pub fn forward(&self) -> Self {
+        let offsets = self
+            .offsets
+            .iter()
+            .filter(|(dx, dy)| *dy < 0 || (*dy == 0 && *dx < 0))
+            .copied()
+            .collect();
+
+        return Neighbourhood { offsets };
+    }
+
+    /// The offsets already visited when the pixel is reached by a raster
+    /// scan going right to left, bottom to top.
+    pub fn backward(&self) -> Self {
+        let offsets = self
+            .offsets
+            .iter()
+            .filter(|(dx, dy)| *dy > 0 || (*dy == 0 && *dx > 0))
+            .copied()
+            .collect();
+
+        return Neighbourhood { offsets };
+    }
+
+    /// The neighbourhood mirrored through the origin.
+    pub fn reflect(&self) -> Self {
+        let offsets = self.offsets.iter().map(|(dx, dy)| (-dx, -dy)).collect();
+        return Neighbourhood { offsets };
     }
 
-    return neighbours;
+    pub fn offsets(&self) -> &[(i32, i32)] {
+        return &self.offsets;
+    }
+
+    /// Largest chessboard distance between the origin and an offset.
+    pub fn radius(&self) -> u32 {
+        return self
+            .offsets
+            .iter()
+            .map(|(dx, dy)| std::cmp::max(dx.unsigned_abs(), dy.unsigned_abs()))
+            .max()
+            .unwrap_or(0);
+    }
+
+    pub fn get_neighbours<P: Primitive>(
+        &self,
+        img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+        coords: (u32, u32),
+    ) -> Vec<(u32, u32)> {
+        return self.get_neighbours_in(img.width(), img.height(), coords);
+    }
+
+    /// Neighbours of `coords` inside a `width` x `height` image.
+    pub fn get_neighbours_in(
+        &self,
+        width: u32,
+        height: u32,
+        coords: (u32, u32),
+    ) -> Vec<(u32, u32)> {
+        let mut neighbours = Vec::with_capacity(self.offsets.len());
+        for (dx, dy) in &self.offsets {
+            let i = coords.0 as i64 + *dx as i64;
+            let j = coords.1 as i64 + *dy as i64;
+
+            if i >= 0 && j >= 0 && i < width as i64 && j < height as i64 {
+                neighbours.push((i as u32, j as u32));
+            }
+        }
+
+        return neighbours;
+    }
+}
+
+impl From<ConnTypes> for Neighbourhood {
+    fn from(conn: ConnTypes) -> Self {
+        return match conn {
+            ConnTypes::Four => Neighbourhood::new(vec![(-1, 0), (0, -1), (0, 1), (1, 0)]),
+            ConnTypes::Eight => Neighbourhood::square(1),
+        };
+    }
+}
+
+impl From<&Neighbourhood> for Neighbourhood {
+    fn from(neighbourhood: &Neighbourhood) -> Self {
+        return neighbourhood.clone();
+    }
+}
+
+pub fn get_pixel_neighbours<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    coords: (u32, u32),
+    conn: impl Into<Neighbourhood>,
+) -> Vec<(u32, u32)> {
+    return conn.into().get_neighbours(img, coords);
 }
 
 pub fn convert_to_binary(
@@ -85,54 +218,66 @@ pub fn is_pixel_in_section<P: Primitive>(pixel: (u32, u32), section: &ParallelSe
 
 pub fn get_upper_border_pixels_coords<P: Primitive>(
     img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-) -> VecDeque<(u32, u32)> {
+    thickness: u32,
+) -> Result<VecDeque<(u32, u32)>> {
+    error::check_not_empty(img)?;
+
     let mut border = VecDeque::new();
     for i in 0..img.width() {
-        for j in 0..1 {
+        for j in 0..std::cmp::min(thickness, img.height()) {
             border.push_back((i, j));
         }
     }
 
-    return border;
+    return Ok(border);
 }
 
 pub fn get_left_border_pixels_coords<P: Primitive>(
     img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-) -> VecDeque<(u32, u32)> {
+    thickness: u32,
+) -> Result<VecDeque<(u32, u32)>> {
+    error::check_not_empty(img)?;
+
     let mut border = VecDeque::new();
-    for i in 0..1 {
+    for i in 0..std::cmp::min(thickness, img.width()) {
         for j in 0..img.height() {
             border.push_back((i, j));
         }
     }
 
-    return border;
+    return Ok(border);
 }
 
 pub fn get_bottom_border_pixels_coords<P: Primitive>(
     img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-) -> VecDeque<(u32, u32)> {
+    thickness: u32,
+) -> Result<VecDeque<(u32, u32)>> {
+    error::check_not_empty(img)?;
+
     let mut border = VecDeque::new();
     for i in 0..img.width() {
-        for j in (img.height() - 1)..img.height() {
+        for j in (img.height() - std::cmp::min(thickness, img.height()))..img.height() {
             border.push_back((i, j));
         }
     }
 
-    return border;
+    return Ok(border);
 }
 
 pub fn get_right_border_pixels_coords<P: Primitive>(
     img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-) -> VecDeque<(u32, u32)> {
+    thickness: u32,
+) -> Result<VecDeque<(u32, u32)>> {
+    error::check_not_empty(img)?;
+
     let mut border = VecDeque::new();
-    for i in (img.width() - 1)..img.width() {
+    for i in (img.width() - std::cmp::min(thickness, img.width()))..img.width() {
         for j in 0..img.height() {
             border.push_back((i, j));
         }
     }
 
-    return border;
+    return Ok(border);
 }
 
 mod tests {
@@ -173,6 +318,43 @@ mod tests {
         assert_eq!(ngbs, expected);
     }
 
+    #[test]
+    fn test_neighbourhood_from_conn() {
+        let four = img::Neighbourhood::from(img::ConnTypes::Four);
+        assert_eq!(four.offsets(), &[(-1, 0), (0, -1), (0, 1), (1, 0)]);
+        assert_eq!(four.radius(), 1);
+
+        let eight = img::Neighbourhood::from(img::ConnTypes::Eight);
+        assert_eq!(eight.offsets().len(), 8);
+        assert_eq!(eight, img::Neighbourhood::square(1));
+    }
+
+    #[test]
+    fn test_neighbourhood_half_masks() {
+        let eight = img::Neighbourhood::from(img::ConnTypes::Eight);
+
+        let forward = eight.forward();
+        assert_eq!(forward.offsets(), &[(-1, -1), (-1, 0), (0, -1), (1, -1)]);
+
+        let backward = eight.backward();
+        assert_eq!(backward.offsets(), &[(-1, 1), (0, 1), (1, 0), (1, 1)]);
+        assert!(forward.reflect().forward().offsets().is_empty());
+    }
+
+    #[test]
+    fn test_neighbourhood_disc_and_knight() {
+        let disc = img::Neighbourhood::disc(2);
+        assert_eq!(disc.offsets().len(), 12);
+        assert_eq!(disc.radius(), 2);
+
+        let mask = examples::_gen_example_img();
+        let ngbs = img::get_pixel_neighbours(&mask, (0, 0), img::Neighbourhood::knight());
+        assert_eq!(ngbs, vec![(1, 2), (2, 1)]);
+
+        let ngbs = img::get_pixel_neighbours(&mask, (0, 0), &disc);
+        assert_eq!(ngbs, vec![(0, 1), (0, 2), (1, 0), (1, 1), (2, 0)]);
+    }
+
     #[test]
     fn test_is_pixel_in_section() {
         let section = parallel_img::ParallelSection {
@@ -182,16 +364,17 @@ mod tests {
             slice: _gen_example_img(),
         };
 
-        assert_eq!(img::is_pixel_in_section((0, 0), &section), true);
-        assert_eq!(img::is_pixel_in_section((2, 2), &section), false);
-        assert_eq!(img::is_pixel_in_section((1, 2), &section), false);
-        assert_eq!(img::is_pixel_in_section((3, 2), &section), false);
+        assert!(img::is_pixel_in_section((0, 0), &section));
+        assert!(!img::is_pixel_in_section((2, 2), &section));
+        assert!(!img::is_pixel_in_section((1, 2), &section));
+        assert!(!img::is_pixel_in_section((3, 2), &section));
     }
 
     #[test]
     fn test_get_upper_border_pixels_coords() {
         let img = _gen_seq_img();
-        let mut upper_border = Vec::from_iter(img::get_upper_border_pixels_coords(&img));
+        let mut upper_border =
+            Vec::from_iter(img::get_upper_border_pixels_coords(&img, 1).unwrap());
         let mut expected: Vec<(u32, u32)> = vec![(0, 0), (1, 0), (2, 0), (3, 0)];
         upper_border.sort();
         expected.sort();
@@ -202,7 +385,7 @@ mod tests {
     #[test]
     fn test_get_left_border_pixels_coords() {
         let img = _gen_seq_img();
-        let mut upper_border = Vec::from_iter(img::get_left_border_pixels_coords(&img));
+        let mut upper_border = Vec::from_iter(img::get_left_border_pixels_coords(&img, 1).unwrap());
         let mut expected: Vec<(u32, u32)> = vec![(0, 0), (0, 1), (0, 2), (0, 3)];
         upper_border.sort();
         expected.sort();
@@ -213,7 +396,8 @@ mod tests {
     #[test]
     fn test_get_bottom_border_pixels_coords() {
         let img = _gen_seq_img();
-        let mut upper_border = Vec::from_iter(img::get_bottom_border_pixels_coords(&img));
+        let mut upper_border =
+            Vec::from_iter(img::get_bottom_border_pixels_coords(&img, 1).unwrap());
         let mut expected: Vec<(u32, u32)> = vec![(0, 3), (1, 3), (2, 3), (3, 3)];
         upper_border.sort();
         expected.sort();
@@ -224,11 +408,26 @@ mod tests {
     #[test]
     fn test_get_right_border_pixels_coords() {
         let img = _gen_seq_img();
-        let mut upper_border = Vec::from_iter(img::get_right_border_pixels_coords(&img));
+        let mut upper_border =
+            Vec::from_iter(img::get_right_border_pixels_coords(&img, 1).unwrap());
         let mut expected: Vec<(u32, u32)> = vec![(3, 0), (3, 1), (3, 2), (3, 3)];
         upper_border.sort();
         expected.sort();
 
         assert_eq!(upper_border, expected);
     }
+
+    #[test]
+    fn test_border_pixels_of_empty_img() {
+        let img = examples::_gen_same_value_image(0, 4, 0u8);
+
+        assert_eq!(
+            img::get_bottom_border_pixels_coords(&img, 1),
+            Err(crate::error::Error::EmptyImage)
+        );
+        assert_eq!(
+            img::get_right_border_pixels_coords(&img, 1),
+            Err(crate::error::Error::EmptyImage)
+        );
+    }
 }
@@ -1,10 +1,188 @@
-use crate::parallel_img::ParallelSection;
+use crate::atomic_img::{AtomicImg, AtomicPrimitive};
+use crate::error::{self, Result};
+use crate::monitor::{Cancelled, Monitor, Progress};
+use crate::parallel_img::{ParallelSection, SectionBounds};
+use crate::scheduler::{self, StealingQueues};
 use crate::{img, parallel_img};
-use image::{ImageBuffer, Luma, Primitive};
-use std::collections::VecDeque;
-use std::sync::Arc;
+use image::{imageops, ImageBuffer, Luma, Primitive};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::sync::Mutex;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Describes how a wavefront spreads from a pixel to its neighbours.
+///
+/// For every pixel popped from the queue, the engine asks `condition` whether
+/// each neighbour should be reached by the wavefront. If so, the neighbour
+/// takes the value returned by `update` and is pushed back into the queue.
+///
+/// Algorithm specific data (a mask, a distance function...) lives in the
+/// implementor itself, so the engine stays generic over every algorithm.
+pub trait Propagator<P: Primitive> {
+    fn condition(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> bool;
+
+    fn update(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> P;
+
+    /// Called every time a neighbour is updated and pushed into the queue.
+    fn on_enqueue(&self, _pixel: img::PixelT<P>) {}
+
+    /// Called once the queue is empty, with the fully propagated image.
+    fn on_finish(&self, _img: &ImageBuffer<Luma<P>, Vec<P>>) {}
+}
+
+/// A `Propagator` built from a pair of closures.
+pub struct FnPropagator<C, U> {
+    condition: C,
+    update: U,
+}
+
+impl<C, U> FnPropagator<C, U> {
+    pub fn new(condition: C, update: U) -> Self {
+        FnPropagator { condition, update }
+    }
+}
+
+impl<P, C, U> Propagator<P> for FnPropagator<C, U>
+where
+    P: Primitive,
+    C: Fn(img::PixelT<P>, img::PixelT<P>) -> bool,
+    U: Fn(img::PixelT<P>, img::PixelT<P>) -> P,
+{
+    fn condition(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> bool {
+        return (self.condition)(curr_pixel, ngb_pixel);
+    }
+
+    fn update(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> P {
+        return (self.update)(curr_pixel, ngb_pixel);
+    }
+}
+
+/// A `Propagator` whose queued pixels must be processed by increasing cost
+/// rather than in arrival order, as in watershed flooding, gray-weighted
+/// distances or the image foresting transform.
+pub trait PriorityPropagator<P: Primitive>: Propagator<P> {
+    type Cost: Ord + Copy;
+
+    /// Cost of a queued pixel, given the value it was queued with.
+    fn cost(&self, pixel: img::PixelT<P>) -> Self::Cost;
+}
+
+/// The order in which the engine visits queued pixels.
+trait Frontier<P: Primitive> {
+    fn push(&mut self, pixel: img::PixelT<P>);
+
+    /// `current` gives the value a queued pixel holds at the time it is popped.
+    fn pop(&mut self, current: impl Fn((u32, u32)) -> P) -> Option<(u32, u32)>;
+
+    fn len(&self) -> usize;
+
+    /// Empties the frontier, keeping the pixels in the order they would
+    /// have been queued.
+    fn take_coords(&mut self) -> Vec<(u32, u32)>;
+}
+
+impl<P: Primitive> Frontier<P> for VecDeque<(u32, u32)> {
+    fn push(&mut self, pixel: img::PixelT<P>) {
+        self.push_back(pixel.coords);
+    }
+
+    fn pop(&mut self, _current: impl Fn((u32, u32)) -> P) -> Option<(u32, u32)> {
+        return self.pop_front();
+    }
+
+    fn len(&self) -> usize {
+        return VecDeque::len(self);
+    }
+
+    fn take_coords(&mut self) -> Vec<(u32, u32)> {
+        return self.drain(..).collect();
+    }
+}
+
+/// Cost, arrival number and coordinates of a pixel in a `PriorityFrontier`.
+type QueuedPixel<C> = (C, u64, (u32, u32));
+
+/// Hierarchical queue: pixels come out by increasing cost and, among equal
+/// costs, in the order they went in.
+struct PriorityFrontier<'a, P: Primitive, F: PriorityPropagator<P>> {
+    heap: BinaryHeap<Reverse<QueuedPixel<F::Cost>>>,
+    count: u64,
+    propagator: &'a F,
+}
+
+impl<'a, P: Primitive, F: PriorityPropagator<P>> PriorityFrontier<'a, P, F> {
+    fn new(propagator: &'a F) -> Self {
+        PriorityFrontier {
+            heap: BinaryHeap::new(),
+            count: 0,
+            propagator,
+        }
+    }
+}
+
+impl<P: Primitive, F: PriorityPropagator<P>> Frontier<P> for PriorityFrontier<'_, P, F> {
+    fn push(&mut self, pixel: img::PixelT<P>) {
+        let cost = self.propagator.cost(pixel);
+        self.heap.push(Reverse((cost, self.count, pixel.coords)));
+        self.count += 1;
+    }
+
+    fn pop(&mut self, current: impl Fn((u32, u32)) -> P) -> Option<(u32, u32)> {
+        while let Some(Reverse((cost, _, coords))) = self.heap.pop() {
+            // A pixel updated after being queued was queued again with its
+            // new cost, so the stale entry is skipped
+            let pixel = img::PixelT {
+                coords,
+                value: current(coords),
+            };
+            if self.propagator.cost(pixel) == cost {
+                return Some(coords);
+            }
+        }
+
+        return None;
+    }
+
+    fn len(&self) -> usize {
+        return self.heap.len();
+    }
+
+    fn take_coords(&mut self) -> Vec<(u32, u32)> {
+        let mut queued = std::mem::take(&mut self.heap).into_vec();
+        queued.sort_by_key(|Reverse((_, count, _))| *count);
+        return queued
+            .into_iter()
+            .map(|Reverse((_, _, coords))| coords)
+            .collect();
+    }
+}
+
+/// The tile based parallel modes split the image into this many sections per
+/// thread, so a thread done with its own sections can steal the remaining
+/// ones of a busier thread instead of sitting idle.
+const SECTIONS_PER_THREAD: u32 = 4;
+
+/// Number of sections the tile based modes split an image into: up to
+/// `SECTIONS_PER_THREAD` per thread, fewer when the image is too small for
+/// that many sections, but never fewer than `num_threads`.
+fn section_count(width: u32, height: u32, num_threads: u32) -> u32 {
+    // Every section needs at least one pixel
+    let num_pixels = width.saturating_mul(height);
+    let max_sections = num_threads
+        .saturating_mul(SECTIONS_PER_THREAD)
+        .min(num_pixels)
+        .max(num_threads);
+
+    return (num_threads..=max_sections)
+        .rev()
+        .find(|n| {
+            *n <= num_pixels
+                && parallel_img::layout(width, height, *n)
+                    .is_ok_and(|bounds| bounds.iter().all(|b| b.width > 0 && b.height > 0))
+        })
+        .unwrap_or(num_threads);
+}
 
 #[derive(Debug)]
 struct IWPSection<'a, P: Primitive> {
@@ -12,28 +190,76 @@ struct IWPSection<'a, P: Primitive> {
     queue: VecDeque<(u32, u32)>,
 }
 
-pub fn propagate<T, P: Primitive>(
+pub fn propagate<P: Primitive, F: Propagator<P>>(
     base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
-    propagation_condition: fn(
-        img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-        curr_pixel: img::PixelT<P>,
-        ngb_pixel: img::PixelT<P>,
-        aux_structure: &T,
-    ) -> bool,
-    update_func: fn(
-        img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-        curr_pixel: img::PixelT<P>,
-        ngb_pixel: img::PixelT<P>,
-        aux_structure: &T,
-    ) -> P,
+    propagator: &F,
     queue: &mut VecDeque<(u32, u32)>,
-    aux_structure: &T,
-) where
-    T: Clone, // check if this makes sense
-{
-    while queue.len() != 0 {
-        let pixel_coords = queue.pop_front().unwrap(); // change this method to a more efficent one
-        let pixel_ngbs = img::get_pixel_neighbours(base_img, pixel_coords, img::ConnTypes::Eight);
+    conn: impl Into<img::Neighbourhood>,
+) {
+    propagate_monitored(base_img, propagator, queue, conn, &Monitor::default())
+        .expect("a propagation without cancel token cannot be cancelled");
+}
+
+/// Same as `propagate`, reporting its progress to `monitor` and stopping when
+/// the monitor's token is cancelled. In that case the image holds the partial
+/// result and `queue` the pixels left to process.
+pub fn propagate_monitored<P: Primitive, F: Propagator<P>>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    monitor: &Monitor,
+) -> Result<(), Cancelled> {
+    let neighbourhood = conn.into();
+    propagate_frontier(base_img, propagator, queue, &neighbourhood, monitor)?;
+    propagator.on_finish(base_img);
+    return Ok(());
+}
+
+/// Same as `propagate`, but queued pixels are processed by increasing
+/// `PriorityPropagator::cost`, ties being broken by arrival order.
+pub fn propagate_priority<P: Primitive, F: PriorityPropagator<P>>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+) {
+    let neighbourhood = conn.into();
+    let mut frontier = PriorityFrontier::new(propagator);
+    for coords in queue.drain(..) {
+        frontier.push(img::PixelT {
+            coords,
+            value: base_img.get_pixel(coords.0, coords.1).0[0],
+        });
+    }
+
+    propagate_frontier(
+        base_img,
+        propagator,
+        &mut frontier,
+        &neighbourhood,
+        &Monitor::default(),
+    )
+    .expect("a propagation without cancel token cannot be cancelled");
+    propagator.on_finish(base_img);
+}
+
+fn propagate_frontier<P: Primitive, F: Propagator<P>, Q: Frontier<P>>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    frontier: &mut Q,
+    neighbourhood: &img::Neighbourhood,
+    monitor: &Monitor,
+) -> Result<(), Cancelled> {
+    let mut processed: u64 = 0;
+    if monitor.is_cancelled() {
+        return Err(Cancelled {
+            pixels_processed: processed,
+        });
+    }
+
+    while let Some(pixel_coords) = frontier.pop(|c| base_img.get_pixel(c.0, c.1).0[0]) {
+        let pixel_ngbs = neighbourhood.get_neighbours(base_img, pixel_coords);
         let curr_pixel = img::PixelT {
             coords: pixel_coords,
             value: base_img.get_pixel(pixel_coords.0, pixel_coords.1).0[0],
@@ -45,197 +271,975 @@ pub fn propagate<T, P: Primitive>(
                 value: base_img.get_pixel(ngb_coord.0, ngb_coord.1).0[0],
             };
 
-            if propagation_condition(base_img, curr_pixel, ngb_pixel, aux_structure) {
-                let new_value = update_func(base_img, curr_pixel, ngb_pixel, aux_structure);
+            if propagator.condition(curr_pixel, ngb_pixel) {
+                let new_value = propagator.update(curr_pixel, ngb_pixel);
 
-                let mut ngb = base_img.get_pixel_mut(ngb_coord.0, ngb_coord.1);
+                let ngb = base_img.get_pixel_mut(ngb_coord.0, ngb_coord.1);
                 ngb.0[0] = new_value;
 
-                queue.push_back(ngb_coord);
+                let pushed = img::PixelT {
+                    coords: ngb_coord,
+                    value: new_value,
+                };
+                propagator.on_enqueue(pushed);
+                frontier.push(pushed);
+            }
+        }
+
+        processed += 1;
+        if monitor.is_due(processed) {
+            monitor.report(Progress {
+                pixels_processed: processed,
+                queue_len: frontier.len(),
+            });
+            if monitor.is_cancelled() {
+                return Err(Cancelled {
+                    pixels_processed: processed,
+                });
             }
         }
     }
+
+    monitor.report(Progress {
+        pixels_processed: processed,
+        queue_len: 0,
+    });
+    return Ok(());
+}
+
+/// Counters gathered while a section runs its own propagation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionStats {
+    pub start: (u32, u32),
+    pub width: u32,
+    pub height: u32,
+    /// Length of the section queue before the propagation started.
+    pub queue_len: usize,
+    /// Pixels popped from the section queue.
+    pub pixels_processed: u64,
+    /// Neighbours whose value was updated.
+    pub updates: u64,
+    /// Time spent looking up neighbours.
+    pub neighbour_time: Duration,
+    pub total_time: Duration,
+}
+
+/// Run statistics returned by `propagate_parallel`, so callers can log or
+/// export them as they see fit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PropagationStats {
+    pub sections: Vec<SectionStats>,
+    /// Length of the queue seeded from the section borders.
+    pub final_stage_queue_len: usize,
+    /// Time spent assembling the sections and propagating across them.
+    pub final_stage_time: Duration,
+}
+
+impl PropagationStats {
+    pub fn pixels_processed(&self) -> u64 {
+        return self.sections.iter().map(|s| s.pixels_processed).sum();
+    }
+
+    pub fn updates(&self) -> u64 {
+        return self.sections.iter().map(|s| s.updates).sum();
+    }
 }
 
-pub fn propagate_parallel<T, P: Primitive + Send + 'static>(
+/// Parallel propagation where every section first propagates on its own,
+/// followed by a sequential final stage seeded from the section borders.
+/// The image is updated in place. Fails when the image cannot be split into
+/// `num_threads` sections.
+pub fn propagate_parallel<P, F>(
     base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
-    propagation_condition: fn(
-        img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-        curr_pixel: img::PixelT<P>,
-        ngb_pixel: img::PixelT<P>,
-        aux_structure: &T,
-    ) -> bool,
-    update_func: fn(
-        img: &image::ImageBuffer<Luma<P>, Vec<P>>,
-        curr_pixel: img::PixelT<P>,
-        ngb_pixel: img::PixelT<P>,
-        aux_structure: &T,
-    ) -> P,
+    propagator: &F,
     queue: &mut VecDeque<(u32, u32)>,
-    aux_structure: &T,
+    conn: impl Into<img::Neighbourhood>,
     num_threads: u32,
-) -> ImageBuffer<Luma<P>, Vec<P>>
+) -> Result<PropagationStats>
 where
-    T: Clone + Send + Sync + 'static,
+    P: Primitive + Send + Sync + 'static,
+    F: Propagator<P> + Sync,
 {
-    let mut parallel_sections: Vec<IWPSection<P>> = Vec::new();
-    let mut sections = parallel_img::arrange(base_img, num_threads);
+    error::check_num_threads(num_threads)?;
+
+    let neighbourhood = conn.into();
+    let mut parallel_sections: Vec<Mutex<IWPSection<P>>> = Vec::new();
+    let num_sections = section_count(base_img.width(), base_img.height(), num_threads);
+    let mut sections = parallel_img::arrange(base_img, num_sections)?;
 
     for section in &mut sections {
         let mut sec_queue: VecDeque<(u32, u32)> = VecDeque::new();
-        for (_, p_coords) in queue.iter().enumerate() {
-            if img::is_pixel_in_section(*p_coords, &section) {
-                //queue.remove(idx);
+        for p_coords in queue.iter() {
+            if img::is_pixel_in_section(*p_coords, section) {
                 sec_queue.push_back(*p_coords);
             }
         }
 
-        parallel_sections.push(IWPSection {
+        parallel_sections.push(Mutex::new(IWPSection {
             section,
             queue: sec_queue,
-        })
+        }))
     }
 
-    thread::scope(|s| {
-        let mut handles = vec![];
-        let aux_s = Arc::new(aux_structure.clone());
-        for section in &mut parallel_sections {
-            let aux_s = Arc::clone(&aux_s);
-            let handle = s.spawn(move || {
-                println!(
-                    "Running thread of section {:?}, queue length = {:?}",
-                    section.section.start,
-                    section.queue.len()
-                );
-                let mut total_time: u128 = 0;
-                let mut count: u128 = 0;
-                let mut get_pixel_ngb_total_time = 0;
-                let mut inner_loop_total_time = 0;
-                while section.queue.len() != 0 {
-                    let now = Instant::now();
-                    let pixel_coords_abs = section.queue.pop_front().unwrap();
-                    let pixel_coords = (
-                        pixel_coords_abs.0 - section.section.start.0,
-                        pixel_coords_abs.1 - section.section.start.1,
-                    );
-
-                    let curr_pixel = section
-                        .section
-                        .get_relative_pixel(pixel_coords.0, pixel_coords.1);
-
-                    let curr_pixel_abs = img::PixelT {
-                        coords: pixel_coords_abs,
-                        value: curr_pixel.value,
-                    };
-
-                    let now_2 = Instant::now();
-                    let pixel_ngbs = img::get_pixel_neighbours(
-                        &section.section.slice,
-                        pixel_coords,
-                        img::ConnTypes::Eight,
-                    );
-                    let elapsed_2 = now_2.elapsed().as_nanos();
-
-                    let now_3 = Instant::now();
-                    for ngb_coord in pixel_ngbs {
-                        let ngb_pixel_abs = section.section.get_abs_pixel(ngb_coord.0, ngb_coord.1);
-
-                        if propagation_condition(
-                            &section.section.slice,
-                            curr_pixel_abs,
-                            ngb_pixel_abs,
-                            &aux_s,
-                        ) {
-                            let new_value = update_func(
-                                &section.section.slice,
-                                curr_pixel_abs,
-                                ngb_pixel_abs,
-                                &aux_s,
-                            );
-
-                            let mut ngb = section
-                                .section
-                                .slice
-                                .get_pixel_mut(ngb_coord.0, ngb_coord.1);
-                            ngb.0[0] = new_value;
-
-                            section.queue.push_back(ngb_pixel_abs.coords);
-                        }
+    let section_stats: Vec<Mutex<SectionStats>> = parallel_sections
+        .iter()
+        .map(|_| Mutex::new(SectionStats::default()))
+        .collect();
+
+    scheduler::run_tasks(parallel_sections.len(), num_threads, |idx| {
+        let mut section = parallel_sections[idx].lock().unwrap();
+        *section_stats[idx].lock().unwrap() =
+            propagate_section(&mut section, propagator, &neighbourhood);
+    });
+    queue.clear();
+
+    let now = Instant::now();
+    let mut queue = VecDeque::new();
+    for section in sections.iter() {
+        let mut active_border_pixels =
+            get_section_active_borders(base_img, section, neighbourhood.radius())?;
+        queue.append(&mut active_border_pixels);
+    }
+    let final_stage_queue_len = queue.len();
+
+    *base_img = parallel_img::get_full_img(base_img.width(), base_img.height(), &sections);
+
+    propagate(base_img, propagator, &mut queue, &neighbourhood);
+
+    return Ok(PropagationStats {
+        sections: section_stats
+            .into_iter()
+            .map(|s| s.into_inner().unwrap())
+            .collect(),
+        final_stage_queue_len,
+        final_stage_time: now.elapsed(),
+    });
+}
+
+fn propagate_section<P: Primitive, F: Propagator<P>>(
+    section: &mut IWPSection<P>,
+    propagator: &F,
+    neighbourhood: &img::Neighbourhood,
+) -> SectionStats {
+    let mut stats = SectionStats {
+        start: section.section.start,
+        width: section.section.width,
+        height: section.section.height,
+        queue_len: section.queue.len(),
+        ..Default::default()
+    };
+
+    let now = Instant::now();
+    while let Some(pixel_coords_abs) = section.queue.pop_front() {
+        let pixel_coords = (
+            pixel_coords_abs.0 - section.section.start.0,
+            pixel_coords_abs.1 - section.section.start.1,
+        );
+
+        let curr_pixel = section
+            .section
+            .get_relative_pixel(pixel_coords.0, pixel_coords.1);
+
+        let curr_pixel_abs = img::PixelT {
+            coords: pixel_coords_abs,
+            value: curr_pixel.value,
+        };
+
+        let now_ngb = Instant::now();
+        let pixel_ngbs = neighbourhood.get_neighbours(&section.section.slice, pixel_coords);
+        stats.neighbour_time += now_ngb.elapsed();
+
+        for ngb_coord in pixel_ngbs {
+            let ngb_pixel_abs = section.section.get_abs_pixel(ngb_coord.0, ngb_coord.1);
+
+            if propagator.condition(curr_pixel_abs, ngb_pixel_abs) {
+                let new_value = propagator.update(curr_pixel_abs, ngb_pixel_abs);
+
+                let ngb = section
+                    .section
+                    .slice
+                    .get_pixel_mut(ngb_coord.0, ngb_coord.1);
+                ngb.0[0] = new_value;
+
+                propagator.on_enqueue(img::PixelT {
+                    coords: ngb_pixel_abs.coords,
+                    value: new_value,
+                });
+                section.queue.push_back(ngb_pixel_abs.coords);
+                stats.updates += 1;
+            }
+        }
+
+        stats.pixels_processed += 1;
+    }
+    stats.total_time = now.elapsed();
+
+    return stats;
+}
+
+/// Parallel propagation where every section keeps working on its own tile
+/// and hands the pixels it processed near its border to the neighbouring
+/// sections, in rounds, until no section has anything left to propagate.
+///
+/// Every stage runs in parallel and, as long as the propagation is order
+/// independent, converging to the same fixed point whatever order pixels are
+/// processed in (as MR does), the result is the same as the one given by
+/// `propagate`. DT is only order independent where its distances are exact
+/// (see `dist_transform`); elsewhere the result may differ. The image is
+/// updated in place.
+pub fn propagate_parallel_exchange<P, F>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<()>
+where
+    P: Primitive + Send + Sync + 'static,
+    F: Propagator<P> + Sync,
+{
+    return propagate_parallel_exchange_monitored(
+        base_img,
+        propagator,
+        queue,
+        conn,
+        num_threads,
+        &Monitor::default(),
+    );
+}
+
+/// Same as `propagate_parallel_exchange`, reporting its progress to `monitor`
+/// after every round and stopping when the monitor's token is cancelled. In
+/// that case it fails with `Error::Cancelled`, the image holds the partial
+/// result and `queue` the pixels left to process, as with
+/// `propagate_monitored`.
+pub fn propagate_parallel_exchange_monitored<P, F>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+    monitor: &Monitor,
+) -> Result<()>
+where
+    P: Primitive + Send + Sync + 'static,
+    F: Propagator<P> + Sync,
+{
+    let neighbourhood = conn.into();
+    return exchange_rounds(
+        base_img,
+        propagator,
+        queue,
+        &neighbourhood,
+        num_threads,
+        VecDeque::new,
+        monitor,
+    );
+}
+
+/// Parallel version of `propagate_priority`, built on the same border
+/// exchange as `propagate_parallel_exchange`. Each section processes its
+/// pixels by increasing cost, so the result matches `propagate_priority`
+/// whenever it does not depend on the order in which equal-cost fronts from
+/// different sections meet (e.g. gray-weighted distances, but not the
+/// watershed lines between basins flooded at the same level).
+pub fn propagate_priority_parallel<P, F>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<()>
+where
+    P: Primitive + Send + Sync + 'static,
+    F: PriorityPropagator<P> + Sync,
+{
+    let neighbourhood = conn.into();
+    return exchange_rounds(
+        base_img,
+        propagator,
+        queue,
+        &neighbourhood,
+        num_threads,
+        || PriorityFrontier::new(propagator),
+        &Monitor::default(),
+    );
+}
+
+fn exchange_rounds<P, F, Q, N>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    neighbourhood: &img::Neighbourhood,
+    num_threads: u32,
+    new_frontier: N,
+    monitor: &Monitor,
+) -> Result<()>
+where
+    P: Primitive + Send + Sync + 'static,
+    F: Propagator<P> + Sync,
+    Q: Frontier<P>,
+    N: Fn() -> Q + Sync,
+{
+    let radius = neighbourhood.radius();
+    let (width, height) = base_img.dimensions();
+
+    let num_sections = section_count(width, height, num_threads);
+    let bounds = parallel_img::layout(width, height, num_sections)?;
+    let halos: Vec<SectionBounds> = bounds
+        .iter()
+        .map(|b| b.expand(radius, width, height))
+        .collect();
+
+    let mut queues: Vec<VecDeque<(u32, u32)>> = vec![VecDeque::new(); bounds.len()];
+    for coords in queue.drain(..) {
+        if let Some(idx) = bounds.iter().position(|b| b.contains(coords)) {
+            queues[idx].push_back(coords);
+        }
+    }
+
+    let mut processed: u64 = 0;
+    let mut cancelled = monitor.is_cancelled();
+    while !cancelled && queues.iter().any(|q| !q.is_empty()) {
+        let active_idxs: Vec<usize> = (0..queues.len())
+            .filter(|idx| !queues[*idx].is_empty())
+            .collect();
+        let tasks: Vec<Mutex<VecDeque<(u32, u32)>>> = active_idxs
+            .iter()
+            .map(|idx| Mutex::new(std::mem::take(&mut queues[*idx])))
+            .collect();
+        let results: Vec<Mutex<Option<_>>> = active_idxs.iter().map(|_| Mutex::new(None)).collect();
+
+        let shared_img = &*base_img;
+        scheduler::run_tasks(tasks.len(), num_threads, |task| {
+            let idx = active_idxs[task];
+            let owned = bounds[idx];
+            let halo = halos[idx];
+            let mut slice = imageops::crop_imm(
+                shared_img,
+                halo.start.0,
+                halo.start.1,
+                halo.width,
+                halo.height,
+            )
+            .to_image();
+
+            let mut seeds = tasks[task].lock().unwrap();
+            let (active, count) = propagate_region(
+                &mut slice,
+                halo,
+                owned,
+                radius,
+                propagator,
+                &mut seeds,
+                neighbourhood,
+                &mut new_frontier(),
+                monitor,
+            );
+
+            *results[task].lock().unwrap() = Some((idx, slice, active, count));
+        });
+
+        let results = results
+            .into_iter()
+            .filter_map(|result| result.into_inner().unwrap());
+
+        for (idx, slice, active, count) in results {
+            processed += count;
+            let owned = bounds[idx];
+            let halo = halos[idx];
+            let owned_slice = imageops::crop_imm(
+                &slice,
+                owned.start.0 - halo.start.0,
+                owned.start.1 - halo.start.1,
+                owned.width,
+                owned.height,
+            );
+            imageops::replace(
+                base_img,
+                &*owned_slice,
+                owned.start.0 as i64,
+                owned.start.1 as i64,
+            );
+
+            for coords in active {
+                for (ngb_idx, ngb_halo) in halos.iter().enumerate() {
+                    if ngb_idx != idx && ngb_halo.contains(coords) {
+                        queues[ngb_idx].push_back(coords);
                     }
-                    let elapsed_3 = now_3.elapsed().as_nanos();
-
-                    let elapsed = now.elapsed().as_nanos();
-                    
-                    total_time += elapsed;
-                    get_pixel_ngb_total_time += elapsed_2;
-                    inner_loop_total_time += elapsed_3;
-                    count += 1;
                 }
-                let avg_iter_time = total_time / count;
-                let avg_get_pixel_ngb = get_pixel_ngb_total_time / count;
-                let avg_inner_loop_time = inner_loop_total_time / count; 
-                println!(
-                    "Finish thread of section {:?}, total_time = {:?}, count = {:?}, average_iter_time = {:?}, average_get_pixel_ngb = {:?}, avg_inner_loop = {:?}",
-                    section.section.start, total_time, count, avg_iter_time, avg_get_pixel_ngb, avg_inner_loop_time
-                );
-            });
+            }
+        }
 
-            handles.push(handle);
+        // Seeds left over by a cancelled section stay queued
+        for (task, seeds) in tasks.into_iter().enumerate() {
+            queues[active_idxs[task]].append(&mut seeds.into_inner().unwrap());
         }
 
-        for handle in handles {
-            handle.join().unwrap();
+        monitor.report(Progress {
+            pixels_processed: processed,
+            queue_len: queues.iter().map(|q| q.len()).sum(),
+        });
+        cancelled = monitor.is_cancelled();
+    }
+
+    if cancelled {
+        // A pixel forwarded to several sections is only handed back once
+        let mut seen = HashSet::new();
+        for coords in queues.into_iter().flatten() {
+            if seen.insert(coords) {
+                queue.push_back(coords);
+            }
+        }
+        return Err(Cancelled {
+            pixels_processed: processed,
+        }
+        .into());
+    }
+
+    propagator.on_finish(base_img);
+    return Ok(());
+}
+
+/// Number of pixels a worker of `propagate_shared` takes from the queues at
+/// once, to keep the locking overhead low.
+const SHARED_BATCH_SIZE: usize = 256;
+
+/// Parallel propagation where every thread works directly on one shared
+/// copy of the image, with no sections to split or stitch back.
+///
+/// Neighbours are updated with compare-and-swap, so the propagation must be
+/// monotone (values only ever move towards the final result, as the max of
+/// MR does) and order independent for the result to match `propagate`, the
+/// same as for `propagate_parallel_exchange`. DT is only so where its
+/// distances are exact (see `dist_transform`).
+/// Each thread keeps its own queue and steals from the others when it runs
+/// out of work. The image is updated in place.
+pub fn propagate_shared<P, F>(
+    base_img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    propagator: &F,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<()>
+where
+    P: AtomicPrimitive,
+    F: Propagator<P> + Sync,
+{
+    error::check_num_threads(num_threads)?;
+
+    let neighbourhood = conn.into();
+    let shared_img = AtomicImg::from_img(base_img);
+    let queues = StealingQueues::new(num_threads as usize);
+
+    let chunk_size = std::cmp::max(1, queue.len().div_ceil(num_threads as usize));
+    for (worker, chunk) in queue
+        .drain(..)
+        .collect::<Vec<_>>()
+        .chunks(chunk_size)
+        .enumerate()
+    {
+        queues.push(worker, chunk.iter().copied());
+    }
+
+    thread::scope(|s| {
+        for worker in 0..queues.num_workers() {
+            let shared_img = &shared_img;
+            let queues = &queues;
+            let neighbourhood = &neighbourhood;
+
+            s.spawn(move || {
+                let mut pushed = Vec::new();
+                while !queues.is_finished() {
+                    let batch = queues.pop(worker, SHARED_BATCH_SIZE);
+                    if batch.is_empty() {
+                        thread::yield_now();
+                        continue;
+                    }
+
+                    for pixel_coords in batch.iter() {
+                        propagate_shared_pixel(
+                            shared_img,
+                            propagator,
+                            *pixel_coords,
+                            neighbourhood,
+                            &mut pushed,
+                        );
+                    }
+
+                    queues.push(worker, pushed.drain(..));
+                    queues.done(batch.len());
+                }
+            });
         }
     });
 
-    println!("Final Stage...");
+    shared_img.write_to(base_img);
+    propagator.on_finish(base_img);
+    return Ok(());
+}
 
-    let mut queue = VecDeque::new();
-    for section in sections.iter() {
-        let mut active_border_pixels = get_section_active_borders(base_img, section);
-        queue.append(&mut active_border_pixels);
+fn propagate_shared_pixel<P: AtomicPrimitive, F: Propagator<P>>(
+    shared_img: &AtomicImg<P>,
+    propagator: &F,
+    pixel_coords: (u32, u32),
+    neighbourhood: &img::Neighbourhood,
+    pushed: &mut Vec<(u32, u32)>,
+) {
+    let curr_pixel = img::PixelT {
+        coords: pixel_coords,
+        value: shared_img.get_pixel(pixel_coords.0, pixel_coords.1),
+    };
+
+    let pixel_ngbs =
+        neighbourhood.get_neighbours_in(shared_img.width(), shared_img.height(), pixel_coords);
+
+    for ngb_coord in pixel_ngbs {
+        let mut ngb_pixel = img::PixelT {
+            coords: ngb_coord,
+            value: shared_img.get_pixel(ngb_coord.0, ngb_coord.1),
+        };
+
+        while propagator.condition(curr_pixel, ngb_pixel) {
+            let new_value = propagator.update(curr_pixel, ngb_pixel);
+
+            match shared_img.compare_exchange(ngb_coord.0, ngb_coord.1, ngb_pixel.value, new_value)
+            {
+                Ok(_) => {
+                    propagator.on_enqueue(img::PixelT {
+                        coords: ngb_coord,
+                        value: new_value,
+                    });
+                    pushed.push(ngb_coord);
+                    break;
+                }
+                // Another thread got there first, check again against its value
+                Err(current) => ngb_pixel.value = current,
+            }
+        }
+    }
+}
+
+/// Runs the wavefront over `slice`, a copy of the `halo` region of the full
+/// image. Pixels outside `owned` are only read, never updated. Returns the
+/// pixels of `owned` processed within `margin` of its edges: the only ones
+/// able to reach the neighbouring sections.
+#[allow(clippy::too_many_arguments)]
+fn propagate_region<P: Primitive, F: Propagator<P>, Q: Frontier<P>>(
+    slice: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    halo: SectionBounds,
+    owned: SectionBounds,
+    margin: u32,
+    propagator: &F,
+    seeds: &mut VecDeque<(u32, u32)>,
+    neighbourhood: &img::Neighbourhood,
+    frontier: &mut Q,
+    monitor: &Monitor,
+) -> (HashSet<(u32, u32)>, u64) {
+    let inner = SectionBounds {
+        start: (owned.start.0 + margin, owned.start.1 + margin),
+        width: owned.width.saturating_sub(2 * margin),
+        height: owned.height.saturating_sub(2 * margin),
+    };
+    let mut active = HashSet::new();
+    let mut processed: u64 = 0;
+
+    let to_local = |coords: (u32, u32)| (coords.0 - halo.start.0, coords.1 - halo.start.1);
+
+    for coords in seeds.drain(..) {
+        let local = to_local(coords);
+        frontier.push(img::PixelT {
+            coords,
+            value: slice.get_pixel(local.0, local.1).0[0],
+        });
     }
 
-    let mut full_img = parallel_img::get_full_img(base_img.width(), base_img.height(), &sections);
+    while let Some(pixel_coords_abs) = frontier.pop(|c| {
+        let local = to_local(c);
+        slice.get_pixel(local.0, local.1).0[0]
+    }) {
+        let pixel_coords = to_local(pixel_coords_abs);
+        let curr_pixel = img::PixelT {
+            coords: pixel_coords_abs,
+            value: slice.get_pixel(pixel_coords.0, pixel_coords.1).0[0],
+        };
+
+        if owned.contains(pixel_coords_abs) && !inner.contains(pixel_coords_abs) {
+            active.insert(pixel_coords_abs);
+        }
 
-    propagate(
-        &mut full_img,
-        propagation_condition,
-        update_func,
-        &mut queue,
-        aux_structure,
-    );
+        for ngb_coord in neighbourhood.get_neighbours(slice, pixel_coords) {
+            let ngb_coord_abs = (ngb_coord.0 + halo.start.0, ngb_coord.1 + halo.start.1);
+            if !owned.contains(ngb_coord_abs) {
+                continue;
+            }
 
-    return full_img;
+            let ngb_pixel = img::PixelT {
+                coords: ngb_coord_abs,
+                value: slice.get_pixel(ngb_coord.0, ngb_coord.1).0[0],
+            };
+
+            if propagator.condition(curr_pixel, ngb_pixel) {
+                let new_value = propagator.update(curr_pixel, ngb_pixel);
+
+                let ngb = slice.get_pixel_mut(ngb_coord.0, ngb_coord.1);
+                ngb.0[0] = new_value;
+
+                let pushed = img::PixelT {
+                    coords: ngb_coord_abs,
+                    value: new_value,
+                };
+                propagator.on_enqueue(pushed);
+                frontier.push(pushed);
+            }
+        }
+
+        processed += 1;
+        if monitor.is_due(processed) && monitor.is_cancelled() {
+            seeds.extend(frontier.take_coords());
+            break;
+        }
+    }
+
+    return (active, processed);
 }
 
+/// Pixels of the section that may propagate into a neighbouring section:
+/// a band as thick as the neighbourhood radius along every inner border.
 fn get_section_active_borders<P: Primitive>(
     base_img: &image::ImageBuffer<Luma<P>, Vec<P>>,
     section: &ParallelSection<P>,
-) -> VecDeque<(u32, u32)> {
+    radius: u32,
+) -> Result<VecDeque<(u32, u32)>> {
     let mut border_pixels = VecDeque::new();
     let x = section.start.0;
     let y = section.start.1;
 
     if x != 0 {
-        border_pixels.append(&mut img::get_left_border_pixels_coords(&section.slice));
+        border_pixels.append(&mut img::get_left_border_pixels_coords(
+            &section.slice,
+            radius,
+        )?);
     }
 
     if y != 0 {
-        border_pixels.append(&mut img::get_upper_border_pixels_coords(&section.slice));
+        border_pixels.append(&mut img::get_upper_border_pixels_coords(
+            &section.slice,
+            radius,
+        )?);
     }
 
     if y + section.height != base_img.height() {
-        border_pixels.append(&mut img::get_bottom_border_pixels_coords(&section.slice));
+        border_pixels.append(&mut img::get_bottom_border_pixels_coords(
+            &section.slice,
+            radius,
+        )?);
     }
 
     if x + section.width != base_img.width() {
-        border_pixels.append(&mut img::get_right_border_pixels_coords(&section.slice));
+        border_pixels.append(&mut img::get_right_border_pixels_coords(
+            &section.slice,
+            radius,
+        )?);
     }
 
     border_pixels
         .iter_mut()
         .for_each(|p| *p = section.get_abs_pixel(p.0, p.1).coords);
 
-    return border_pixels;
+    return Ok(border_pixels);
+}
+
+mod tests {
+
+    #![allow(unused_imports)]
+
+    use crate::error::Error;
+    use crate::examples::*;
+    use crate::img::{ConnTypes, PixelT};
+    use crate::iwp::*;
+
+    #[test]
+    fn test_propagate_with_closures() {
+        let mask = _gen_big_mask_img();
+        let mut marker = _gen_big_marker_img();
+
+        let mut initial: VecDeque<(u32, u32)> = VecDeque::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                if marker.get_pixel(i, j).0[0] != 8 {
+                    initial.push_back((i, j));
+                }
+            }
+        }
+
+        let propagator = FnPropagator::new(
+            |curr: PixelT<u8>, ngb: PixelT<u8>| {
+                let mask_ngb = mask.get_pixel(ngb.coords.0, ngb.coords.1).0[0];
+                ngb.value < curr.value && ngb.value != mask_ngb
+            },
+            |curr: PixelT<u8>, ngb: PixelT<u8>| {
+                let mask_ngb = mask.get_pixel(ngb.coords.0, ngb.coords.1).0[0];
+                std::cmp::min(curr.value, mask_ngb)
+            },
+        );
+
+        propagate(&mut marker, &propagator, &mut initial, ConnTypes::Eight);
+
+        assert_eq!(marker, _gen_expected_img());
+    }
+
+    #[test]
+    fn test_propagator_hooks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Floods the zeros from the seed and records every hook call
+        struct Recorder {
+            enqueued: AtomicUsize,
+            finished: Mutex<Vec<Vec<u8>>>,
+        }
+
+        impl Propagator<u8> for Recorder {
+            fn condition(&self, _curr: PixelT<u8>, ngb: PixelT<u8>) -> bool {
+                return ngb.value == 0;
+            }
+
+            fn update(&self, curr: PixelT<u8>, _ngb: PixelT<u8>) -> u8 {
+                return curr.value;
+            }
+
+            fn on_enqueue(&self, pixel: PixelT<u8>) {
+                assert_eq!(pixel.value, 7);
+                self.enqueued.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn on_finish(&self, img: &ImageBuffer<Luma<u8>, Vec<u8>>) {
+                self.finished.lock().unwrap().push(img.as_raw().clone());
+            }
+        }
+
+        let mut img = _gen_same_value_image(6, 4, 0u8);
+        img.put_pixel(2, 1, Luma([7]));
+
+        let recorder = Recorder {
+            enqueued: AtomicUsize::new(0),
+            finished: Mutex::new(vec![]),
+        };
+        let mut result = img.clone();
+        propagate(
+            &mut result,
+            &recorder,
+            &mut VecDeque::from([(2, 1)]),
+            ConnTypes::Four,
+        );
+
+        assert_eq!(result, _gen_same_value_image(6, 4, 7u8));
+        assert_eq!(recorder.enqueued.load(Ordering::Relaxed), 23);
+        assert_eq!(*recorder.finished.lock().unwrap(), vec![vec![7u8; 24]]);
+
+        let recorder = Recorder {
+            enqueued: AtomicUsize::new(0),
+            finished: Mutex::new(vec![]),
+        };
+        let mut result = img.clone();
+        propagate_parallel_exchange(
+            &mut result,
+            &recorder,
+            &mut VecDeque::from([(2, 1)]),
+            ConnTypes::Four,
+            3,
+        )
+        .unwrap();
+
+        assert_eq!(result, _gen_same_value_image(6, 4, 7u8));
+        assert_eq!(recorder.enqueued.load(Ordering::Relaxed), 23);
+        assert_eq!(*recorder.finished.lock().unwrap(), vec![vec![7u8; 24]]);
+    }
+
+    #[test]
+    fn test_propagate_parallel_small_images() {
+        // Too small for SECTIONS_PER_THREAD sections per thread, but not for
+        // one section per thread
+        assert_eq!(section_count(3, 1, 1), 2);
+        assert_eq!(section_count(1, 1, 1), 1);
+        assert_eq!(section_count(100, 100, 2), 8);
+        assert_eq!(section_count(3, 1, 1 << 30), 1 << 30);
+
+        let flood = FnPropagator::new(
+            |_curr: PixelT<u8>, ngb: PixelT<u8>| ngb.value == 0,
+            |curr: PixelT<u8>, _ngb: PixelT<u8>| curr.value,
+        );
+
+        for (width, height, num_threads) in [(3, 1, 1), (1, 1, 1), (4, 2, 2)] {
+            let mut img = _gen_same_value_image(width, height, 0u8);
+            img.put_pixel(0, 0, Luma([3]));
+
+            let stats = propagate_parallel(
+                &mut img,
+                &flood,
+                &mut VecDeque::from([(0, 0)]),
+                ConnTypes::Four,
+                num_threads,
+            )
+            .unwrap();
+
+            assert_eq!(img, _gen_same_value_image(width, height, 3u8));
+            assert!(stats.sections.len() >= num_threads as usize);
+
+            let mut img = _gen_same_value_image(width, height, 0u8);
+            img.put_pixel(0, 0, Luma([3]));
+            propagate_parallel_exchange(
+                &mut img,
+                &flood,
+                &mut VecDeque::from([(0, 0)]),
+                ConnTypes::Four,
+                num_threads,
+            )
+            .unwrap();
+            assert_eq!(img, _gen_same_value_image(width, height, 3u8));
+        }
+    }
+
+    #[test]
+    fn test_parallel_modes_reject_zero_threads() {
+        struct Flood;
+
+        impl Propagator<u8> for Flood {
+            fn condition(&self, _curr: PixelT<u8>, ngb: PixelT<u8>) -> bool {
+                return ngb.value == 0;
+            }
+
+            fn update(&self, curr: PixelT<u8>, _ngb: PixelT<u8>) -> u8 {
+                return curr.value;
+            }
+        }
+
+        impl PriorityPropagator<u8> for Flood {
+            type Cost = u8;
+
+            fn cost(&self, _pixel: PixelT<u8>) -> u8 {
+                return 0;
+            }
+        }
+
+        let flood = Flood;
+        let mut img = _gen_same_value_image(4, 3, 0u8);
+        img.put_pixel(0, 0, Luma([3]));
+        let seeds = VecDeque::from([(0, 0)]);
+
+        assert_eq!(
+            propagate_parallel_exchange(&mut img, &flood, &mut seeds.clone(), ConnTypes::Four, 0),
+            Err(Error::NoThreads)
+        );
+        assert_eq!(
+            propagate_shared(&mut img, &flood, &mut seeds.clone(), ConnTypes::Four, 0),
+            Err(Error::NoThreads)
+        );
+        assert_eq!(
+            propagate_parallel(&mut img, &flood, &mut seeds.clone(), ConnTypes::Four, 0),
+            Err(Error::NoThreads)
+        );
+        assert_eq!(
+            propagate_priority_parallel(&mut img, &flood, &mut seeds.clone(), ConnTypes::Four, 0),
+            Err(Error::NoThreads)
+        );
+        assert_eq!(parallel_img::layout(4, 3, 0), Err(Error::NoThreads));
+    }
+
+    #[test]
+    fn test_propagate_priority_weighted_distance() {
+        struct WeightedDistance {
+            weights: image::ImageBuffer<Luma<u32>, Vec<u32>>,
+        }
+
+        impl Propagator<u32> for WeightedDistance {
+            fn condition(&self, curr: PixelT<u32>, ngb: PixelT<u32>) -> bool {
+                let weight = self.weights.get_pixel(ngb.coords.0, ngb.coords.1).0[0];
+                return curr.value.saturating_add(weight) < ngb.value;
+            }
+
+            fn update(&self, curr: PixelT<u32>, ngb: PixelT<u32>) -> u32 {
+                let weight = self.weights.get_pixel(ngb.coords.0, ngb.coords.1).0[0];
+                return curr.value + weight;
+            }
+        }
+
+        impl PriorityPropagator<u32> for WeightedDistance {
+            type Cost = u32;
+
+            fn cost(&self, pixel: PixelT<u32>) -> u32 {
+                return pixel.value;
+            }
+        }
+
+        let weights = image::ImageBuffer::from_fn(47, 31, |x, y| Luma([1 + (x * 7 + y * 3) % 5]));
+        let propagator = WeightedDistance { weights };
+
+        let mut fifo = image::ImageBuffer::from_pixel(47, 31, Luma([u32::MAX]));
+        let mut queue = VecDeque::new();
+        for seed in [(0, 0), (30, 20), (46, 3)] {
+            fifo.put_pixel(seed.0, seed.1, Luma([0]));
+            queue.push_back(seed);
+        }
+        let mut priority = fifo.clone();
+        let mut parallel = fifo.clone();
+
+        propagate(&mut fifo, &propagator, &mut queue.clone(), ConnTypes::Eight);
+        propagate_priority(
+            &mut priority,
+            &propagator,
+            &mut queue.clone(),
+            ConnTypes::Eight,
+        );
+        propagate_priority_parallel(&mut parallel, &propagator, &mut queue, ConnTypes::Eight, 4)
+            .unwrap();
+
+        assert_eq!(priority, fifo);
+        assert_eq!(parallel, fifo);
+    }
+
+    #[test]
+    fn test_propagate_priority_stable_ties() {
+        // Flat relief: every pixel has the same cost, so two basins flood
+        // in the order their seeds were queued
+        struct Flood;
+
+        impl Propagator<u8> for Flood {
+            fn condition(&self, _curr: PixelT<u8>, ngb: PixelT<u8>) -> bool {
+                return ngb.value == 0;
+            }
+
+            fn update(&self, curr: PixelT<u8>, _ngb: PixelT<u8>) -> u8 {
+                return curr.value;
+            }
+        }
+
+        impl PriorityPropagator<u8> for Flood {
+            type Cost = u8;
+
+            fn cost(&self, _pixel: PixelT<u8>) -> u8 {
+                return 0;
+            }
+        }
+
+        let labels = image::ImageBuffer::from_raw(5, 1, vec![1u8, 0, 0, 0, 2]).unwrap();
+
+        let mut forward = labels.clone();
+        propagate_priority(
+            &mut forward,
+            &Flood,
+            &mut VecDeque::from([(0, 0), (4, 0)]),
+            ConnTypes::Four,
+        );
+        assert_eq!(forward.into_raw(), vec![1, 1, 1, 2, 2]);
+
+        let mut backward = labels.clone();
+        propagate_priority(
+            &mut backward,
+            &Flood,
+            &mut VecDeque::from([(4, 0), (0, 0)]),
+            ConnTypes::Four,
+        );
+        assert_eq!(backward.into_raw(), vec![1, 1, 2, 2, 2]);
+    }
 }
@@ -1,11 +1,33 @@
+mod area;
+pub mod atomic_img;
 mod dist_transform;
+mod error;
 pub mod examples;
 pub mod format;
 mod img;
 pub mod iwp;
+pub mod monitor;
+mod morph;
 mod mr;
 pub mod parallel_img;
+mod scheduler;
 
-pub use crate::dist_transform::{dist_transform, dist_transform_parallel, DistTypes};
-pub use crate::img::{convert_to_binary, get_pixel_neighbours, ConnTypes, PixelT};
-pub use crate::mr::{morph_reconstruction, morph_reconstruction_parallel};
+pub use crate::area::{area_closing, area_opening};
+pub use crate::dist_transform::{
+    dist_to_u8, dist_transform, dist_transform_parallel, dist_transform_shared,
+    dist_transform_with_spacing, euclidean_dist_transform, feature_transform,
+    signed_dist_transform, squared_euclidean_dist_transform, DistScaling, DistTypes, FeatureMap,
+};
+pub use crate::error::{Error, Result};
+pub use crate::img::{convert_to_binary, get_pixel_neighbours, ConnTypes, Neighbourhood, PixelT};
+pub use crate::monitor::{CancelToken, Cancelled, Monitor, Progress};
+pub use crate::morph::{
+    clear_border, closing_by_reconstruction, dilate, erode, extended_maxima, extended_minima,
+    fill_holes, geodesic_dilate, geodesic_erode, h_dome, h_maxima, h_minima, label_regions,
+    opening_by_reconstruction, regional_maxima, regional_minima, SaturatingPrimitive,
+};
+pub use crate::mr::{
+    morph_reconstruction, morph_reconstruction_erosion, morph_reconstruction_erosion_parallel,
+    morph_reconstruction_monitored, morph_reconstruction_parallel,
+    morph_reconstruction_parallel_monitored, morph_reconstruction_shared,
+};
@@ -1,4 +1,3 @@
-use image;
 use image::io::Reader as ImageReader;
 use imagepkg::{self, convert_to_binary};
 use std::error::Error;
@@ -11,7 +10,11 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     //print_image_by_row(&bin_img);
 
-    let res = imagepkg::dist_transform(&mut bin_img, imagepkg::DistTypes::Euclidean);
+    let res = imagepkg::dist_transform(
+        &mut bin_img,
+        imagepkg::DistTypes::Euclidean,
+        imagepkg::ConnTypes::Eight,
+    )?;
 
     // let img_marker = ImageReader::open("marker.png")?.decode()?;
     // let mut marker = img_marker.to_luma8();
@@ -21,7 +24,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // imagepkg::morph_reconstruction(&mut mask, &mut marker);
 
-    res.save("result_bin_2.png")?;
+    imagepkg::dist_to_u8(&res, imagepkg::DistScaling::Clamp).save("result_bin_2.png")?;
 
     Ok(())
 }
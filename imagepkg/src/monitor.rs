@@ -0,0 +1,143 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Number of processed pixels between two progress reports (and
+/// cancellation checks) when no interval is given.
+const PROGRESS_INTERVAL: u64 = 1 << 16;
+
+/// Flag shared between a running propagation and whoever may want to stop
+/// it, e.g. another thread serving a user request.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        return CancelToken::default();
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of a running propagation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub pixels_processed: u64,
+    /// Pixels still waiting to be processed.
+    pub queue_len: usize,
+}
+
+/// Returned when a propagation is stopped through its `CancelToken`.
+///
+/// The image holds the partial result and the queue given to the engine
+/// holds the pixels left to process, so calling the engine again with both
+/// finishes the propagation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    pub pixels_processed: u64,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(
+            f,
+            "propagation cancelled after {} pixels",
+            self.pixels_processed
+        );
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Optional progress callback and cancellation token for a propagation.
+///
+/// ```
+/// use imagepkg::{CancelToken, Monitor};
+///
+/// let token = CancelToken::new();
+/// let monitor = Monitor::new()
+///     .on_progress(|p| eprintln!("{} pixels, {} queued", p.pixels_processed, p.queue_len))
+///     .cancel_token(token.clone())
+///     .interval(10_000);
+/// ```
+pub struct Monitor<'a> {
+    progress: Option<Box<dyn Fn(Progress) + Sync + 'a>>,
+    cancel: Option<CancelToken>,
+    interval: u64,
+}
+
+impl Default for Monitor<'_> {
+    fn default() -> Self {
+        Monitor {
+            progress: None,
+            cancel: None,
+            interval: PROGRESS_INTERVAL,
+        }
+    }
+}
+
+impl<'a> Monitor<'a> {
+    pub fn new() -> Self {
+        return Monitor::default();
+    }
+
+    /// Called every `interval` processed pixels and once the propagation is
+    /// done. The parallel engines call it between rounds instead.
+    pub fn on_progress(mut self, progress: impl Fn(Progress) + Sync + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        return self;
+    }
+
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        return self;
+    }
+
+    /// Number of processed pixels between two reports and cancellation
+    /// checks.
+    pub fn interval(mut self, pixels: u64) -> Self {
+        self.interval = std::cmp::max(pixels, 1);
+        return self;
+    }
+
+    pub(crate) fn report(&self, progress: Progress) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        return self.cancel.as_ref().is_some_and(|t| t.is_cancelled());
+    }
+
+    /// Whether `pixels_processed` falls on a report boundary.
+    pub(crate) fn is_due(&self, pixels_processed: u64) -> bool {
+        return pixels_processed.is_multiple_of(self.interval);
+    }
+}
+
+mod tests {
+
+    #![allow(unused_imports)]
+
+    use crate::monitor::*;
+
+    #[test]
+    fn cancel_token_is_shared() {
+        let token = CancelToken::new();
+        let monitor = Monitor::new().cancel_token(token.clone());
+
+        assert!(!monitor.is_cancelled());
+        token.cancel();
+        assert!(monitor.is_cancelled());
+        assert!(!Monitor::new().is_cancelled());
+    }
+}
@@ -0,0 +1,847 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use image::{ImageBuffer, Luma, Primitive};
+
+use crate::error::{self, Error, Result};
+use crate::monitor::Monitor;
+use crate::mr::{self, Reconstruction};
+use crate::{img, iwp};
+
+/// A pixel type with the arithmetic needed by the extrema and h-transforms,
+/// saturating at the bounds of the type instead of wrapping around.
+pub trait SaturatingPrimitive: Primitive {
+    /// The closest value below `self`, or `self` at the bottom of the type.
+    fn pred(self) -> Self;
+
+    /// The closest value above `self`, or `self` at the top of the type.
+    fn succ(self) -> Self;
+
+    fn saturating_sub(self, rhs: Self) -> Self;
+
+    fn saturating_add(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_saturating_int {
+    ($($prim:ty),*) => {$(
+        impl SaturatingPrimitive for $prim {
+            fn pred(self) -> Self {
+                return <$prim>::saturating_sub(self, 1);
+            }
+
+            fn succ(self) -> Self {
+                return <$prim>::saturating_add(self, 1);
+            }
+
+            fn saturating_sub(self, rhs: Self) -> Self {
+                return <$prim>::saturating_sub(self, rhs);
+            }
+
+            fn saturating_add(self, rhs: Self) -> Self {
+                return <$prim>::saturating_add(self, rhs);
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_saturating_float {
+    ($($prim:ty),*) => {$(
+        impl SaturatingPrimitive for $prim {
+            fn pred(self) -> Self {
+                return self.next_down();
+            }
+
+            fn succ(self) -> Self {
+                return self.next_up();
+            }
+
+            // Floats do not wrap around, they go to infinity
+            fn saturating_sub(self, rhs: Self) -> Self {
+                return self - rhs;
+            }
+
+            fn saturating_add(self, rhs: Self) -> Self {
+                return self + rhs;
+            }
+        }
+    )*};
+}
+
+impl_saturating_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+impl_saturating_float!(f32, f64);
+
+/// Every pixel of the image frame. Corners appear twice.
+fn get_border_pixels_coords<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+) -> Result<VecDeque<(u32, u32)>> {
+    let mut border = img::get_upper_border_pixels_coords(img, 1)?;
+    border.append(&mut img::get_left_border_pixels_coords(img, 1)?);
+    border.append(&mut img::get_bottom_border_pixels_coords(img, 1)?);
+    border.append(&mut img::get_right_border_pixels_coords(img, 1)?);
+
+    return Ok(border);
+}
+
+fn get_max_value<P: Primitive>(img: &image::ImageBuffer<Luma<P>, Vec<P>>) -> P {
+    return img
+        .pixels()
+        .map(|p| p.0[0])
+        .fold(img.get_pixel(0, 0).0[0], |a, b| if b > a { b } else { a });
+}
+
+fn get_min_value<P: Primitive>(img: &image::ImageBuffer<Luma<P>, Vec<P>>) -> P {
+    return img
+        .pixels()
+        .map(|p| p.0[0])
+        .fold(img.get_pixel(0, 0).0[0], |a, b| if b < a { b } else { a });
+}
+
+/// Fills the holes of `img`, as MATLAB `imfill(img, 'holes')`: the dark
+/// regions that cannot be reached from the image border are raised to the
+/// level of the brighter pixels around them. Works on binary images (see
+/// `convert_to_binary`) as well as on grayscale ones.
+///
+/// `conn` is the connectivity of the background, usually `ConnTypes::Four`
+/// for 8-connected objects.
+pub fn fill_holes<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+
+    // The marker is the image on its border and as high as possible inside,
+    // so eroding it under the image only lowers what the border can reach
+    let mut marker = ImageBuffer::from_pixel(img.width(), img.height(), Luma([get_max_value(img)]));
+    let mut border = get_border_pixels_coords(img)?;
+    for coords in border.iter() {
+        marker.put_pixel(coords.0, coords.1, *img.get_pixel(coords.0, coords.1));
+    }
+
+    mr::reconstruct_from(img, &mut marker, &mut border, conn, Reconstruction::Erosion)?;
+    return Ok(marker);
+}
+
+/// Removes the structures of `img` connected to its border, as MATLAB
+/// `imclearborder`: the part of the image reconstructed from its border is
+/// subtracted from it. On binary images, the objects touching the border
+/// are dropped and the others kept as they are.
+pub fn clear_border<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+
+    // The marker is the image on its border and as low as possible inside,
+    // zero unless the image goes below it
+    let lowest = get_min_value(img);
+    let lowest = if lowest < P::zero() {
+        lowest
+    } else {
+        P::zero()
+    };
+    let mut marker = ImageBuffer::from_pixel(img.width(), img.height(), Luma([lowest]));
+    let mut border = get_border_pixels_coords(img)?;
+    for coords in border.iter() {
+        marker.put_pixel(coords.0, coords.1, *img.get_pixel(coords.0, coords.1));
+    }
+
+    mr::reconstruct_from(
+        img,
+        &mut marker,
+        &mut border,
+        conn,
+        Reconstruction::Dilation,
+    )?;
+
+    for (x, y, pixel) in marker.enumerate_pixels_mut() {
+        pixel.0[0] = img.get_pixel(x, y).0[0].saturating_sub(pixel.0[0]);
+    }
+    return Ok(marker);
+}
+
+/// Binary image (0 or 1) of the pixels where `lower` is below `upper`.
+fn get_difference_mask<P: Primitive>(
+    lower: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    upper: &image::ImageBuffer<Luma<P>, Vec<P>>,
+) -> image::ImageBuffer<Luma<u8>, Vec<u8>> {
+    return ImageBuffer::from_fn(lower.width(), lower.height(), |x, y| {
+        Luma([(lower.get_pixel(x, y).0[0] < upper.get_pixel(x, y).0[0]) as u8])
+    });
+}
+
+fn regional_extrema<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    direction: Reconstruction,
+) -> Result<image::ImageBuffer<Luma<u8>, Vec<u8>>> {
+    error::check_not_empty(img)?;
+
+    // A flat image is a single plateau without any lower (or higher)
+    // boundary, which shifting by one step cannot detect at the type bounds
+    let first = img.get_pixel(0, 0).0[0];
+    if img.pixels().all(|p| p.0[0] == first) {
+        return Ok(ImageBuffer::from_pixel(
+            img.width(),
+            img.height(),
+            Luma([1]),
+        ));
+    }
+
+    // Shifting the image by one step and reconstructing it under the image
+    // gives back the image everywhere but on the extrema, which no higher
+    // (lower) pixel can reach
+    let mut marker = img.clone();
+    for pixel in marker.pixels_mut() {
+        pixel.0[0] = match direction {
+            Reconstruction::Dilation => pixel.0[0].pred(),
+            Reconstruction::Erosion => pixel.0[0].succ(),
+        };
+    }
+
+    mr::reconstruct(img, &mut marker, conn, direction, &Monitor::default())?;
+
+    return match direction {
+        Reconstruction::Dilation => Ok(get_difference_mask(&marker, img)),
+        Reconstruction::Erosion => Ok(get_difference_mask(img, &marker)),
+    };
+}
+
+/// Binary image (0 or 1) of the regional maxima of `img`: the connected
+/// plateaus whose neighbours are all strictly lower, as MATLAB
+/// `imregionalmax`.
+pub fn regional_maxima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u8>, Vec<u8>>> {
+    return regional_extrema(img, conn, Reconstruction::Dilation);
+}
+
+/// Binary image (0 or 1) of the regional minima of `img`: the connected
+/// plateaus whose neighbours are all strictly higher, as MATLAB
+/// `imregionalmin`.
+pub fn regional_minima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u8>, Vec<u8>>> {
+    return regional_extrema(img, conn, Reconstruction::Erosion);
+}
+
+fn check_height<P: Primitive>(h: P) -> Result<()> {
+    // NaN compares to nothing and is rejected as well
+    if !matches!(
+        h.partial_cmp(&P::zero()),
+        Some(Ordering::Greater | Ordering::Equal)
+    ) {
+        return Err(Error::InvalidArgument {
+            name: "h",
+            reason: "the height must be positive",
+        });
+    }
+
+    return Ok(());
+}
+
+fn h_transform<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+    direction: Reconstruction,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+    check_height(h)?;
+
+    let mut marker = img.clone();
+    for pixel in marker.pixels_mut() {
+        pixel.0[0] = match direction {
+            Reconstruction::Dilation => pixel.0[0].saturating_sub(h),
+            Reconstruction::Erosion => pixel.0[0].saturating_add(h),
+        };
+    }
+
+    mr::reconstruct(img, &mut marker, conn, direction, &Monitor::default())?;
+    return Ok(marker);
+}
+
+/// Suppresses the maxima of `img` whose height is below `h`, as MATLAB
+/// `imhmax`: the image minus `h` is reconstructed by dilation under `img`.
+pub fn h_maxima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return h_transform(img, h, conn, Reconstruction::Dilation);
+}
+
+/// Suppresses the minima of `img` whose depth is below `h`, as MATLAB
+/// `imhmin`: the image plus `h` is reconstructed by erosion over `img`.
+pub fn h_minima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return h_transform(img, h, conn, Reconstruction::Erosion);
+}
+
+/// The domes of `img`: `img` minus its `h_maxima`, the top (at most `h`
+/// high) of every bright structure.
+pub fn h_dome<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    let mut dome = h_maxima(img, h, conn)?;
+    for (x, y, pixel) in dome.enumerate_pixels_mut() {
+        pixel.0[0] = img.get_pixel(x, y).0[0].saturating_sub(pixel.0[0]);
+    }
+
+    return Ok(dome);
+}
+
+/// Binary image (0 or 1) of the regional maxima of `h_maxima(img, h)`, as
+/// MATLAB `imextendedmax`: the peaks that stand more than `h` above their
+/// surroundings, each one a single region whatever small bumps it carries.
+pub fn extended_maxima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u8>, Vec<u8>>> {
+    let conn = conn.into();
+    return regional_maxima(&h_maxima(img, h, &conn)?, &conn);
+}
+
+/// Binary image (0 or 1) of the regional minima of `h_minima(img, h)`, as
+/// MATLAB `imextendedmin`.
+pub fn extended_minima<P: SaturatingPrimitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    h: P,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u8>, Vec<u8>>> {
+    let conn = conn.into();
+    return regional_minima(&h_minima(img, h, &conn)?, &conn);
+}
+
+/// Spreads the label of a pixel to the unlabelled foreground around it.
+struct LabelPropagator<'a> {
+    binary: &'a image::ImageBuffer<Luma<u8>, Vec<u8>>,
+}
+
+impl iwp::Propagator<u32> for LabelPropagator<'_> {
+    fn condition(&self, _curr_pixel: img::PixelT<u32>, ngb_pixel: img::PixelT<u32>) -> bool {
+        let ngb_coords = ngb_pixel.coords;
+        return ngb_pixel.value == 0 && self.binary.get_pixel(ngb_coords.0, ngb_coords.1).0[0] != 0;
+    }
+
+    fn update(&self, curr_pixel: img::PixelT<u32>, _ngb_pixel: img::PixelT<u32>) -> u32 {
+        return curr_pixel.value;
+    }
+}
+
+/// Numbers the connected regions of non-zero pixels of `binary` from 1, in
+/// raster order of their first pixel, leaving the background at 0. Turns the
+/// masks of `extended_maxima` and friends into watershed markers.
+pub fn label_regions(
+    binary: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<u32>, Vec<u32>>> {
+    error::check_not_empty(binary)?;
+
+    let conn = conn.into();
+    let propagator = LabelPropagator { binary };
+    let mut labels: image::ImageBuffer<Luma<u32>, Vec<u32>> =
+        ImageBuffer::new(binary.width(), binary.height());
+    let mut count = 0;
+
+    for (x, y, pixel) in binary.enumerate_pixels() {
+        if pixel.0[0] == 0 || labels.get_pixel(x, y).0[0] != 0 {
+            continue;
+        }
+
+        count += 1;
+        labels.put_pixel(x, y, Luma([count]));
+        iwp::propagate(
+            &mut labels,
+            &propagator,
+            &mut VecDeque::from([(x, y)]),
+            &conn,
+        );
+    }
+
+    return Ok(labels);
+}
+
+/// Every pixel takes the furthest value, in the direction values move, of
+/// the pixels covered by `se` centred on it (the pixel itself included).
+fn flat_filter<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    se: &img::Neighbourhood,
+    direction: Reconstruction,
+) -> image::ImageBuffer<Luma<P>, Vec<P>> {
+    return ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let mut value = img.get_pixel(x, y).0[0];
+        for ngb_coords in se.get_neighbours(img, (x, y)) {
+            let ngb_value = img.get_pixel(ngb_coords.0, ngb_coords.1).0[0];
+            value = direction.furthest(value, ngb_value);
+        }
+
+        Luma([value])
+    });
+}
+
+/// Grayscale erosion by the flat structuring element `se`: every pixel takes
+/// the minimum of the pixels under `se` centred on it, the pixel itself
+/// included. Pixels outside the image are ignored.
+pub fn erode<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    se: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+    return Ok(flat_filter(img, &se.into(), Reconstruction::Erosion));
+}
+
+/// Grayscale dilation by the flat structuring element `se`, the dual of
+/// `erode`: every pixel takes the maximum of the pixels under the reflected
+/// `se` centred on it.
+pub fn dilate<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    se: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(img)?;
+    return Ok(flat_filter(
+        img,
+        &se.into().reflect(),
+        Reconstruction::Dilation,
+    ));
+}
+
+fn geodesic_steps<P: Primitive>(
+    marker: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    n: u32,
+    conn: impl Into<img::Neighbourhood>,
+    direction: Reconstruction,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    error::check_not_empty(mask)?;
+    error::check_same_dimensions(mask, marker)?;
+
+    let se = match direction {
+        Reconstruction::Dilation => conn.into().reflect(),
+        Reconstruction::Erosion => conn.into(),
+    };
+
+    let mut result = marker.clone();
+    for _ in 0..n {
+        let mut step = flat_filter(&result, &se, direction);
+        for (x, y, pixel) in step.enumerate_pixels_mut() {
+            pixel.0[0] = direction.clip(pixel.0[0], mask.get_pixel(x, y).0[0]);
+        }
+
+        // Later steps would not change anything either
+        if step == result {
+            break;
+        }
+        result = step;
+    }
+
+    return Ok(result);
+}
+
+/// `n` steps of geodesic dilation of `marker` under `mask`: each step
+/// dilates by `conn` and clips the result to `mask`. As `n` grows, the
+/// result converges to `morph_reconstruction(mask, marker, conn)`, which
+/// makes this useful to look at the reconstruction front at a given step.
+pub fn geodesic_dilate<P: Primitive>(
+    marker: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    n: u32,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return geodesic_steps(marker, mask, n, conn, Reconstruction::Dilation);
+}
+
+/// `n` steps of geodesic erosion of `marker` over `mask`, the dual of
+/// `geodesic_dilate`, converging to `morph_reconstruction_erosion`.
+pub fn geodesic_erode<P: Primitive>(
+    marker: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    n: u32,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return geodesic_steps(marker, mask, n, conn, Reconstruction::Erosion);
+}
+
+/// Erodes `img` by `se`, then reconstructs the erosion by dilation under
+/// `img`: the bright structures `se` does not fit in disappear, while the
+/// others get their exact shape back, unlike with a plain opening.
+pub fn opening_by_reconstruction<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    se: impl Into<img::Neighbourhood>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    let mut marker = erode(img, se)?;
+    mr::reconstruct(
+        img,
+        &mut marker,
+        conn,
+        Reconstruction::Dilation,
+        &Monitor::default(),
+    )?;
+    return Ok(marker);
+}
+
+/// Dilates `img` by `se`, then reconstructs the dilation by erosion over
+/// `img`, the dual of `opening_by_reconstruction` for dark structures.
+pub fn closing_by_reconstruction<P: Primitive>(
+    img: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    se: impl Into<img::Neighbourhood>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    let mut marker = dilate(img, se)?;
+    mr::reconstruct(
+        img,
+        &mut marker,
+        conn,
+        Reconstruction::Erosion,
+        &Monitor::default(),
+    )?;
+    return Ok(marker);
+}
+
+mod tests {
+
+    #![allow(unused_imports)]
+
+    use crate::examples::{_gen_big_marker_img, _gen_big_mask_img, _gen_same_value_image};
+    use crate::img::{ConnTypes, Neighbourhood};
+    use crate::morph::*;
+
+    #[test]
+    fn test_fill_holes_binary() {
+        // A ring with a hole, and a "C" whose inside touches the border
+        let rows: [&[u8]; 6] = [
+            &[0, 0, 0, 0, 0, 1, 1],
+            &[0, 1, 1, 1, 0, 1, 0],
+            &[0, 1, 0, 1, 0, 1, 0],
+            &[0, 1, 0, 1, 0, 1, 0],
+            &[0, 1, 1, 1, 0, 1, 1],
+            &[0, 0, 0, 0, 0, 0, 0],
+        ];
+        let img = ImageBuffer::from_raw(7, 6, rows.concat()).unwrap();
+
+        let mut expected = img.clone();
+        expected.put_pixel(2, 2, Luma([1]));
+        expected.put_pixel(2, 3, Luma([1]));
+
+        assert_eq!(fill_holes(&img, ConnTypes::Four).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_fill_holes_diagonal_leak() {
+        // The hole only reaches the border through a diagonal, so it is a
+        // hole for a 4-connected background but not for an 8-connected one
+        let rows: [&[u8]; 4] = [&[1, 1, 1, 0], &[1, 0, 0, 1], &[1, 0, 0, 1], &[1, 1, 1, 1]];
+        let img = ImageBuffer::from_raw(4, 4, rows.concat()).unwrap();
+
+        let mut filled = img.clone();
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            filled.put_pixel(x, y, Luma([1]));
+        }
+
+        assert_eq!(fill_holes(&img, ConnTypes::Four).unwrap(), filled);
+        assert_eq!(fill_holes(&img, ConnTypes::Eight).unwrap(), img);
+    }
+
+    #[test]
+    fn test_fill_holes_gray() {
+        // A basin at 2 inside a rim at 7 fills up to the rim, the one open
+        // to the border does not
+        let rows: [&[u16]; 5] = [
+            &[3, 3, 3, 3, 3, 3],
+            &[3, 7, 7, 7, 9, 3],
+            &[3, 7, 2, 5, 7, 3],
+            &[3, 7, 7, 7, 7, 1],
+            &[3, 3, 3, 3, 3, 3],
+        ];
+        let img = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+
+        let mut expected = img.clone();
+        expected.put_pixel(2, 2, Luma([7]));
+        expected.put_pixel(3, 2, Luma([7]));
+
+        assert_eq!(fill_holes(&img, ConnTypes::Four).unwrap(), expected);
+        assert_eq!(
+            fill_holes(&_gen_same_value_image(0, 0, 0u8), ConnTypes::Four),
+            Err(crate::error::Error::EmptyImage)
+        );
+    }
+
+    #[test]
+    fn test_regional_extrema() {
+        // A plateau at 5 with a lower pixel next to it, a peak at 9, a
+        // plateau at 7 that spills into the 9, and a pit at 0 in a corner
+        let rows: [&[u8]; 5] = [
+            &[0, 2, 2, 2, 2, 2],
+            &[2, 5, 5, 2, 7, 7],
+            &[2, 5, 5, 2, 7, 9],
+            &[2, 2, 2, 2, 2, 2],
+            &[3, 3, 2, 1, 2, 2],
+        ];
+        let img = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+
+        let rows: [&[u8]; 5] = [
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 1, 1, 0, 0, 0],
+            &[0, 1, 1, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[1, 1, 0, 0, 0, 0],
+        ];
+        let mut maxima = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+        maxima.put_pixel(5, 2, Luma([1]));
+        let rows: [&[u8]; 5] = [
+            &[1, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 1, 0, 0],
+        ];
+        let minima = ImageBuffer::from_raw(6, 5, rows.concat()).unwrap();
+
+        assert_eq!(regional_maxima(&img, ConnTypes::Eight).unwrap(), maxima);
+        assert_eq!(regional_minima(&img, ConnTypes::Eight).unwrap(), minima);
+
+        // Same answer at every depth
+        let img_f: ImageBuffer<Luma<f32>, Vec<f32>> =
+            ImageBuffer::from_fn(6, 5, |x, y| Luma([img.get_pixel(x, y).0[0] as f32 / 9.0]));
+        let img_i: ImageBuffer<Luma<i16>, Vec<i16>> =
+            ImageBuffer::from_fn(6, 5, |x, y| Luma([img.get_pixel(x, y).0[0] as i16 - 4]));
+        assert_eq!(regional_maxima(&img_f, ConnTypes::Eight).unwrap(), maxima);
+        assert_eq!(regional_minima(&img_i, ConnTypes::Eight).unwrap(), minima);
+
+        let flat = _gen_same_value_image(3, 2, 0u8);
+        assert_eq!(
+            regional_maxima(&flat, ConnTypes::Four).unwrap(),
+            _gen_same_value_image(3, 2, 1u8)
+        );
+    }
+
+    #[test]
+    fn test_h_transforms() {
+        // Two peaks, 3 and 6 above the background at 2
+        let row: Vec<u8> = vec![2, 5, 2, 2, 8, 8, 2];
+        let img = ImageBuffer::from_raw(7, 1, row).unwrap();
+
+        let h_max = h_maxima(&img, 4, ConnTypes::Eight).unwrap();
+        assert_eq!(h_max.into_raw(), vec![2, 2, 2, 2, 4, 4, 2]);
+
+        let dome = h_dome(&img, 4, ConnTypes::Eight).unwrap();
+        assert_eq!(dome.into_raw(), vec![0, 3, 0, 0, 4, 4, 0]);
+
+        // Two pits, one only 1 deep
+        let row: Vec<u8> = vec![9, 8, 9, 9, 1, 9, 9];
+        let img = ImageBuffer::from_raw(7, 1, row).unwrap();
+        let h_min = h_minima(&img, 3, ConnTypes::Eight).unwrap();
+        assert_eq!(h_min.into_raw(), vec![9, 9, 9, 9, 4, 9, 9]);
+    }
+
+    #[test]
+    fn test_h_transforms_saturate() {
+        // Subtracting h from the 1s would wrap around without saturation
+        let row: Vec<u16> = vec![1, 1, 3, 1, 65535, 65534];
+        let img = ImageBuffer::from_raw(6, 1, row).unwrap();
+
+        let h_max = h_maxima(&img, 2, ConnTypes::Four).unwrap();
+        assert_eq!(h_max.into_raw(), vec![1, 1, 1, 1, 65533, 65533]);
+
+        let h_min = h_minima(&img, 2, ConnTypes::Four).unwrap();
+        assert_eq!(h_min.into_raw(), vec![3, 3, 3, 3, 65535, 65535]);
+
+        let img: ImageBuffer<Luma<f32>, Vec<f32>> =
+            ImageBuffer::from_raw(3, 1, vec![0.0, 0.75, 0.0]).unwrap();
+        let h_max = h_maxima(&img, 0.5, ConnTypes::Four).unwrap();
+        assert_eq!(h_max.into_raw(), vec![0.0, 0.25, 0.0]);
+        assert_eq!(
+            h_maxima(&img, f32::NAN, ConnTypes::Four),
+            Err(Error::InvalidArgument {
+                name: "h",
+                reason: "the height must be positive"
+            })
+        );
+    }
+
+    #[test]
+    fn test_extended_extrema() {
+        // A bumpy peak (6 and 7 on top of 4) and a small bump of 1
+        let row: Vec<u8> = vec![2, 4, 6, 5, 7, 4, 2, 3, 2];
+        let img = ImageBuffer::from_raw(9, 1, row).unwrap();
+
+        let ext_max = extended_maxima(&img, 2, ConnTypes::Eight).unwrap();
+        assert_eq!(ext_max.into_raw(), vec![0, 0, 1, 1, 1, 0, 0, 0, 0]);
+        let reg_max = regional_maxima(&img, ConnTypes::Eight).unwrap();
+        assert_eq!(reg_max.into_raw(), vec![0, 0, 1, 0, 1, 0, 0, 1, 0]);
+
+        let mut inv = img.clone();
+        image::imageops::invert(&mut inv);
+        let ext_min = extended_minima(&inv, 2, ConnTypes::Eight).unwrap();
+        assert_eq!(ext_min.into_raw(), vec![0, 0, 1, 1, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_label_regions() {
+        let rows: [&[u8]; 4] = [
+            &[1, 1, 0, 0, 1],
+            &[0, 0, 1, 0, 1],
+            &[0, 0, 0, 0, 0],
+            &[1, 0, 0, 1, 1],
+        ];
+        let binary = ImageBuffer::from_raw(5, 4, rows.concat()).unwrap();
+
+        let rows: [&[u32]; 4] = [
+            &[1, 1, 0, 0, 2],
+            &[0, 0, 1, 0, 2],
+            &[0, 0, 0, 0, 0],
+            &[3, 0, 0, 4, 4],
+        ];
+        let expected = ImageBuffer::from_raw(5, 4, rows.concat()).unwrap();
+        assert_eq!(label_regions(&binary, ConnTypes::Eight).unwrap(), expected);
+
+        let rows: [&[u32]; 4] = [
+            &[1, 1, 0, 0, 2],
+            &[0, 0, 3, 0, 2],
+            &[0, 0, 0, 0, 0],
+            &[4, 0, 0, 5, 5],
+        ];
+        let expected = ImageBuffer::from_raw(5, 4, rows.concat()).unwrap();
+        assert_eq!(label_regions(&binary, ConnTypes::Four).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_erode_dilate() {
+        let row: Vec<u8> = vec![1, 5, 2, 2, 8, 3];
+        let img = ImageBuffer::from_raw(6, 1, row).unwrap();
+
+        let square = Neighbourhood::square(1);
+        assert_eq!(
+            erode(&img, &square).unwrap().into_raw(),
+            vec![1, 1, 2, 2, 2, 3]
+        );
+        assert_eq!(
+            dilate(&img, &square).unwrap().into_raw(),
+            vec![5, 5, 5, 8, 8, 8]
+        );
+
+        // Eroding looks to the right, while dilating by the reflected element
+        // looks to the left, so it spreads values to the right
+        let right = Neighbourhood::new(vec![(1, 0)]);
+        assert_eq!(
+            erode(&img, &right).unwrap().into_raw(),
+            vec![1, 2, 2, 2, 3, 3]
+        );
+        assert_eq!(
+            dilate(&img, &right).unwrap().into_raw(),
+            vec![1, 5, 5, 2, 8, 8]
+        );
+    }
+
+    #[test]
+    fn test_opening_closing_by_reconstruction() {
+        // A 3 x 3 square with a notch, and a lone pixel the square SE cannot
+        // fit in
+        let rows: [&[u8]; 6] = [
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 9, 9, 9, 0, 0],
+            &[0, 9, 9, 9, 9, 0],
+            &[0, 9, 9, 9, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 7],
+        ];
+        let img = ImageBuffer::from_raw(6, 6, rows.concat()).unwrap();
+
+        let mut expected = img.clone();
+        expected.put_pixel(5, 5, Luma([0]));
+
+        let opened = opening_by_reconstruction(&img, Neighbourhood::square(1), ConnTypes::Eight);
+        assert_eq!(opened.unwrap(), expected);
+
+        let mut inv = img.clone();
+        image::imageops::invert(&mut inv);
+        image::imageops::invert(&mut expected);
+        let closed = closing_by_reconstruction(&inv, Neighbourhood::square(1), ConnTypes::Eight);
+        assert_eq!(closed.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_clear_border() {
+        // One object touching the left border, one only touching it through
+        // a diagonal, and one inside
+        let rows: [&[u8]; 6] = [
+            &[0, 0, 0, 0, 0, 0],
+            &[1, 1, 0, 0, 0, 0],
+            &[0, 1, 0, 0, 1, 0],
+            &[0, 0, 0, 0, 1, 0],
+            &[0, 0, 0, 0, 0, 0],
+            &[0, 0, 0, 0, 0, 0],
+        ];
+        let mut img = ImageBuffer::from_raw(6, 6, rows.concat()).unwrap();
+        img.put_pixel(1, 4, Luma([1]));
+
+        let mut four = img.clone();
+        four.put_pixel(0, 1, Luma([0]));
+        four.put_pixel(1, 1, Luma([0]));
+        four.put_pixel(1, 2, Luma([0]));
+        assert_eq!(clear_border(&img, ConnTypes::Four).unwrap(), four);
+
+        img.put_pixel(0, 5, Luma([1]));
+        let mut eight = four.clone();
+        eight.put_pixel(1, 4, Luma([0]));
+        assert_eq!(clear_border(&img, ConnTypes::Eight).unwrap(), eight);
+
+        // Grayscale: the plateau at 3 spills into the border at 6 and is
+        // cleared, the peak at 1 only keeps what rises above the border
+        let rows: [&[i16]; 4] = [
+            &[-2, -2, -2, -2, -2, -2],
+            &[-2, 1, -2, 3, 3, -2],
+            &[-2, 1, -2, -2, 3, 6],
+            &[-2, -2, -2, -2, -2, -2],
+        ];
+        let img = ImageBuffer::from_raw(6, 4, rows.concat()).unwrap();
+
+        let mut expected = _gen_same_value_image(6, 4, 0i16);
+        expected.put_pixel(1, 1, Luma([3]));
+        expected.put_pixel(1, 2, Luma([3]));
+        assert_eq!(clear_border(&img, ConnTypes::Four).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_geodesic_steps() {
+        let mask: ImageBuffer<Luma<u8>, Vec<u8>> =
+            ImageBuffer::from_raw(6, 1, vec![5, 5, 3, 6, 6, 0]).unwrap();
+        let marker = ImageBuffer::from_raw(6, 1, vec![5, 0, 0, 0, 0, 0]).unwrap();
+
+        let steps = |n| {
+            geodesic_dilate(&marker, &mask, n, ConnTypes::Four)
+                .unwrap()
+                .into_raw()
+        };
+        assert_eq!(steps(0), vec![5, 0, 0, 0, 0, 0]);
+        assert_eq!(steps(1), vec![5, 5, 0, 0, 0, 0]);
+        assert_eq!(steps(2), vec![5, 5, 3, 0, 0, 0]);
+        assert_eq!(steps(4), vec![5, 5, 3, 3, 3, 0]);
+
+        let mut inv_mask = mask.clone();
+        image::imageops::invert(&mut inv_mask);
+        let mut inv_marker = marker.clone();
+        image::imageops::invert(&mut inv_marker);
+        let mut eroded = geodesic_erode(&inv_marker, &inv_mask, 2, ConnTypes::Four).unwrap();
+        image::imageops::invert(&mut eroded);
+        assert_eq!(eroded.into_raw(), steps(2));
+    }
+
+    #[test]
+    fn test_geodesic_dilate_converges() {
+        let mut mask = _gen_big_mask_img();
+        let mut marker = _gen_big_marker_img();
+
+        let dilated = geodesic_dilate(&marker, &mask, 1000, ConnTypes::Eight).unwrap();
+        crate::mr::morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight).unwrap();
+        assert_eq!(dilated, marker);
+    }
+}
@@ -1,48 +1,89 @@
+use crate::atomic_img::AtomicPrimitive;
+use crate::error::{self, Result};
+use crate::monitor::Monitor;
 use crate::{img, iwp, parallel_img};
-use image::Luma;
+use image::{Luma, Primitive};
 use std::{
     collections::{HashSet, VecDeque},
     thread,
 };
 
-fn update_pixel(
+/// The way marker values move during a reconstruction: up towards the mask
+/// (by dilation) or down towards it (by erosion).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Reconstruction {
+    Dilation,
+    Erosion,
+}
+
+impl Reconstruction {
+    /// Whether `a` is further than `b` in the direction values move.
+    pub(crate) fn beyond<P: Primitive>(self, a: P, b: P) -> bool {
+        return match self {
+            Reconstruction::Dilation => a > b,
+            Reconstruction::Erosion => a < b,
+        };
+    }
+
+    /// The value among `a` and `b` that is the furthest in the direction
+    /// values move.
+    pub(crate) fn furthest<P: Primitive>(self, a: P, b: P) -> P {
+        if self.beyond(b, a) {
+            return b;
+        }
+
+        return a;
+    }
+
+    /// `value` stopped at `bound`, the mask value of the pixel.
+    pub(crate) fn clip<P: Primitive>(self, value: P, bound: P) -> P {
+        if self.beyond(value, bound) {
+            return bound;
+        }
+
+        return value;
+    }
+}
+
+fn update_pixel<P: Primitive>(
     pixel_coords: (u32, u32),
-    mask: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    marker: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: &img::Neighbourhood,
+    direction: Reconstruction,
 ) {
-    let pixel_ngbs = img::get_pixel_neighbours(marker, pixel_coords, img::ConnTypes::Eight);
+    let pixel_ngbs = conn.get_neighbours(marker, pixel_coords);
 
     let pixel = marker.get_pixel(pixel_coords.0, pixel_coords.1);
-    let mut greater = pixel.0[0];
+    let mut furthest = pixel.0[0];
     for ngb_coord in &pixel_ngbs {
         let ngb = marker.get_pixel(ngb_coord.0, ngb_coord.1);
-
-        if ngb.0[0] > greater {
-            greater = ngb.0[0];
-        }
+        furthest = direction.furthest(furthest, ngb.0[0]);
     }
 
-    let mut pixel = marker.get_pixel_mut(pixel_coords.0, pixel_coords.1);
+    let pixel = marker.get_pixel_mut(pixel_coords.0, pixel_coords.1);
     let mask_pixel = mask.get_pixel(pixel_coords.0, pixel_coords.1);
 
-    if greater > mask_pixel.0[0] {
-        greater = mask_pixel.0[0];
-    }
-
-    pixel.0[0] = greater;
+    pixel.0[0] = direction.clip(furthest, mask_pixel.0[0]);
 }
 
-fn get_initial_pixels(
-    mask: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    marker: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
+fn get_initial_pixels<P: Primitive>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: &img::Neighbourhood,
+    direction: Reconstruction,
 ) -> VecDeque<(u32, u32)> {
     let width = marker.width();
     let height = marker.height();
     let mut queue = HashSet::new();
 
+    // The propagation pushes values from a pixel to its neighbours, so the
+    // scans pull them from the pixels having it as a neighbour
+    let pull_conn = conn.reflect();
+
     for i in 0..height {
         for j in 0..width {
-            update_pixel((j, i), mask, marker);
+            update_pixel((j, i), mask, marker, &pull_conn, direction);
         }
     }
 
@@ -50,17 +91,19 @@ fn get_initial_pixels(
         for j in (0..width).rev() {
             let pixel_coords = (j, i);
 
-            update_pixel((j, i), mask, marker);
+            update_pixel((j, i), mask, marker, &pull_conn, direction);
             let pixel_marker = marker.get_pixel(pixel_coords.0, pixel_coords.1);
             let pixel_value = pixel_marker.0[0];
 
-            let pixel_ngbs = img::get_pixel_neighbours(marker, pixel_coords, img::ConnTypes::Eight);
+            let pixel_ngbs = conn.get_neighbours(marker, pixel_coords);
 
             for ngb_coord in pixel_ngbs {
                 let ngb = marker.get_pixel(ngb_coord.0, ngb_coord.1);
                 let ngb_mask = mask.get_pixel(ngb_coord.0, ngb_coord.1);
 
-                if (ngb.0[0] < pixel_value) && (ngb.0[0] < ngb_mask.0[0]) {
+                if direction.beyond(pixel_value, ngb.0[0])
+                    && direction.beyond(ngb_mask.0[0], ngb.0[0])
+                {
                     queue.insert(ngb_coord);
                 }
             }
@@ -71,31 +114,26 @@ fn get_initial_pixels(
     return queue;
 }
 
-fn get_initial_pixels_parallel(
-    mask: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    marker: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
+#[allow(clippy::type_complexity)]
+fn get_initial_pixels_parallel<P: Primitive + Send + Sync + 'static>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: &img::Neighbourhood,
     num_threads: u32,
-) -> (image::ImageBuffer<Luma<u8>, Vec<u8>>, VecDeque<(u32, u32)>) {
+    direction: Reconstruction,
+) -> Result<(image::ImageBuffer<Luma<P>, Vec<P>>, VecDeque<(u32, u32)>)> {
     //let mask_arc = Arc::new(mask.clone());
-    let mut sections = parallel_img::arrange(marker, num_threads);
-    let mask_sections = parallel_img::arrange(&mut mask.clone(), num_threads);
-
-    for section in sections.iter() {
-        println!(
-            "Creating of section {:?}, width = {:?}, height = {:?}",
-            section.start, section.width, section.height
-        );
-    }
+    let mut sections = parallel_img::arrange(marker, num_threads)?;
+    let mask_sections = parallel_img::arrange(&mut mask.clone(), num_threads)?;
 
-    let queue = thread::scope(|s| {
+    let mut queue = thread::scope(|s| {
         let mut handles = vec![];
-        let mut count = 0;
-        for section in &mut sections {
+        for (count, section) in sections.iter_mut().enumerate() {
             let mask_section_slice = &mask_sections.get(count).unwrap().slice;
 
             let handle = s.spawn(move || {
                 let mut relative_queue =
-                    get_initial_pixels(&mask_section_slice, &mut section.slice);
+                    get_initial_pixels(mask_section_slice, &mut section.slice, conn, direction);
                 relative_queue
                     .iter_mut()
                     .for_each(|p| *p = (p.0 + section.start.0, p.1 + section.start.1));
@@ -103,8 +141,6 @@ fn get_initial_pixels_parallel(
             });
 
             handles.push(handle);
-
-            count += 1;
         }
 
         // Chech if creating a HashSet is really necessary - since it adds a little overhead
@@ -121,78 +157,270 @@ fn get_initial_pixels_parallel(
         return queue;
     });
 
+    // The raster scans only look inside each section, so the pixels able to
+    // propagate into a neighbouring section must be seeded as well
+    for section in sections.iter() {
+        let bounds = parallel_img::SectionBounds {
+            start: section.start,
+            width: section.width,
+            height: section.height,
+        };
+        queue.extend(bounds.border_band(conn.radius()));
+    }
+
     let full_img = parallel_img::get_full_img(marker.width(), marker.height(), &sections);
 
-    return (full_img, VecDeque::from_iter(queue));
+    return Ok((full_img, VecDeque::from_iter(queue)));
+}
+
+/// A pixel moves towards its neighbour's value, but never beyond the mask.
+struct MRPropagator<'a, P: Primitive> {
+    mask: &'a image::ImageBuffer<Luma<P>, Vec<P>>,
+    direction: Reconstruction,
+}
+
+impl<'a, P: Primitive> MRPropagator<'a, P> {
+    fn dilation(mask: &'a image::ImageBuffer<Luma<P>, Vec<P>>) -> Self {
+        return MRPropagator {
+            mask,
+            direction: Reconstruction::Dilation,
+        };
+    }
 }
 
-fn propagation_condition(
-    _marker: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    curr_pixel: img::PixelT<u8>,
-    ngb_pixel: img::PixelT<u8>,
-    mask: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-) -> bool {
-    let mask_ngb = mask.get_pixel(ngb_pixel.coords.0, ngb_pixel.coords.1);
-    if (ngb_pixel.value < curr_pixel.value) && (mask_ngb.0[0] != ngb_pixel.value) {
-        return true;
+impl<P: Primitive> iwp::Propagator<P> for MRPropagator<'_, P> {
+    fn condition(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> bool {
+        let mask_ngb = self.mask.get_pixel(ngb_pixel.coords.0, ngb_pixel.coords.1);
+        if self.direction.beyond(curr_pixel.value, ngb_pixel.value)
+            && (mask_ngb.0[0] != ngb_pixel.value)
+        {
+            return true;
+        }
+
+        return false;
     }
 
-    return false;
+    fn update(&self, curr_pixel: img::PixelT<P>, ngb_pixel: img::PixelT<P>) -> P {
+        let mask_ngb = self.mask.get_pixel(ngb_pixel.coords.0, ngb_pixel.coords.1);
+        return self.direction.clip(curr_pixel.value, mask_ngb.0[0]);
+    }
 }
 
-fn update_func(
-    _marker: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    curr_pixel: img::PixelT<u8>,
-    ngb_pixel: img::PixelT<u8>,
-    mask: &image::ImageBuffer<Luma<u8>, Vec<u8>>,
-) -> u8 {
-    let mask_ngb = mask.get_pixel(ngb_pixel.coords.0, ngb_pixel.coords.1);
-    return std::cmp::min(curr_pixel.value, mask_ngb.0[0]);
+/// Both images must have the same, non-zero, dimensions.
+fn check_inputs<P: Primitive>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &image::ImageBuffer<Luma<P>, Vec<P>>,
+) -> Result<()> {
+    error::check_not_empty(mask)?;
+    error::check_same_dimensions(mask, marker)?;
+    return Ok(());
 }
 
-pub fn morph_reconstruction(
-    mask: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    marker: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
-) {
-    let mut initial_queue = get_initial_pixels(&mask, marker);
-    iwp::propagate(
+pub(crate) fn reconstruct<P: Primitive>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    direction: Reconstruction,
+    monitor: &Monitor,
+) -> Result<()> {
+    check_inputs(mask, marker)?;
+
+    let conn = conn.into();
+    let mut initial_queue = get_initial_pixels(mask, marker, &conn, direction);
+    iwp::propagate_monitored(
         marker,
-        propagation_condition,
-        update_func,
+        &MRPropagator { mask, direction },
+        &mut initial_queue,
+        &conn,
+        monitor,
+    )?;
+    return Ok(());
+}
+
+/// Same as `reconstruct`, when the only pixels of `marker` able to propagate
+/// are known up front and given in `queue`, which skips the raster scans.
+pub(crate) fn reconstruct_from<P: Primitive>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    queue: &mut VecDeque<(u32, u32)>,
+    conn: impl Into<img::Neighbourhood>,
+    direction: Reconstruction,
+) -> Result<()> {
+    check_inputs(mask, marker)?;
+
+    iwp::propagate(marker, &MRPropagator { mask, direction }, queue, conn);
+    return Ok(());
+}
+
+fn reconstruct_parallel<P: Primitive + Send + Sync + 'static>(
+    mask: &image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+    direction: Reconstruction,
+    monitor: &Monitor,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    check_inputs(mask, marker)?;
+    error::check_num_threads(num_threads)?;
+
+    let conn = conn.into();
+    let (mut base_img, mut initial_queue) =
+        get_initial_pixels_parallel(mask, marker, &conn, num_threads, direction)?;
+
+    let propagation = iwp::propagate_parallel_exchange_monitored(
+        &mut base_img,
+        &MRPropagator { mask, direction },
         &mut initial_queue,
+        &conn,
+        num_threads,
+        monitor,
+    );
+    if let Err(err) = propagation {
+        *marker = base_img;
+        return Err(err);
+    }
+
+    return Ok(base_img);
+}
+
+/// Reconstruction by dilation: the marker grows towards the mask, in place.
+pub fn morph_reconstruction<P: Primitive>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<()> {
+    return reconstruct(
         mask,
+        marker,
+        conn,
+        Reconstruction::Dilation,
+        &Monitor::default(),
     );
 }
 
-pub fn morph_reconstruction_parallel(
-    mask: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
-    marker: &mut image::ImageBuffer<Luma<u8>, Vec<u8>>,
+/// Same as `morph_reconstruction`, reporting its progress to `monitor` and
+/// stopping when the monitor's token is cancelled, leaving the partial
+/// reconstruction in `marker`.
+pub fn morph_reconstruction_monitored<P: Primitive>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    monitor: &Monitor,
+) -> Result<()> {
+    return reconstruct(mask, marker, conn, Reconstruction::Dilation, monitor);
+}
+
+pub fn morph_reconstruction_parallel<P: Primitive + Send + Sync + 'static>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
     num_threads: u32,
-) -> image::ImageBuffer<image::Luma<u8>, Vec<u8>> {
-    let (mut base_img, mut initial_queue) = get_initial_pixels_parallel(&mask, marker, num_threads);
-    let result = iwp::propagate_parallel(
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return reconstruct_parallel(
+        mask,
+        marker,
+        conn,
+        num_threads,
+        Reconstruction::Dilation,
+        &Monitor::default(),
+    );
+}
+
+/// Same as `morph_reconstruction_parallel`, reporting its progress to
+/// `monitor` after every exchange round and stopping when the monitor's
+/// token is cancelled. In that case `marker` is replaced by the partial
+/// reconstruction, which a later call finishes.
+pub fn morph_reconstruction_parallel_monitored<P: Primitive + Send + Sync + 'static>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+    monitor: &Monitor,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return reconstruct_parallel(
+        mask,
+        marker,
+        conn,
+        num_threads,
+        Reconstruction::Dilation,
+        monitor,
+    );
+}
+
+/// Same as `morph_reconstruction_parallel`, but every thread propagates over
+/// one shared image instead of its own section.
+pub fn morph_reconstruction_shared<P: AtomicPrimitive + 'static>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    check_inputs(mask, marker)?;
+    error::check_num_threads(num_threads)?;
+
+    // The raster scans run over the whole image: with no sections, there
+    // are no section borders to seed afterwards
+    let conn = conn.into();
+    let mut base_img = marker.clone();
+    let mut initial_queue =
+        get_initial_pixels(mask, &mut base_img, &conn, Reconstruction::Dilation);
+
+    iwp::propagate_shared(
         &mut base_img,
-        propagation_condition,
-        update_func,
+        &MRPropagator::dilation(mask),
         &mut initial_queue,
+        &conn,
+        num_threads,
+    )?;
+    return Ok(base_img);
+}
+
+/// Reconstruction by erosion, the dual of `morph_reconstruction`: the marker
+/// (above the mask) shrinks towards the mask, in place.
+pub fn morph_reconstruction_erosion<P: Primitive>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+) -> Result<()> {
+    return reconstruct(
         mask,
+        marker,
+        conn,
+        Reconstruction::Erosion,
+        &Monitor::default(),
+    );
+}
+
+pub fn morph_reconstruction_erosion_parallel<P: Primitive + Send + Sync + 'static>(
+    mask: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    marker: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    conn: impl Into<img::Neighbourhood>,
+    num_threads: u32,
+) -> Result<image::ImageBuffer<Luma<P>, Vec<P>>> {
+    return reconstruct_parallel(
+        mask,
+        marker,
+        conn,
         num_threads,
+        Reconstruction::Erosion,
+        &Monitor::default(),
     );
-    return result;
 }
 
 mod tests {
 
     #![allow(unused_imports)]
 
+    use std::sync::Mutex;
     use std::time::Instant;
 
+    use crate::error::Error;
     use crate::examples::*;
     use crate::format;
-    use crate::img::is_pixel_in_section;
+    use crate::img::{is_pixel_in_section, ConnTypes, Neighbourhood};
     use crate::iwp;
+    use crate::monitor::{CancelToken, Cancelled};
     use crate::mr::*;
-    use image::io::Reader as ImageReader;
 
     #[test]
     fn test_get_initial_pixels() {
@@ -201,7 +429,12 @@ mod tests {
         let mut marker = _gen_same_value_image(6, 6, 0);
         marker.put_pixel(4, 4, Luma([1]));
 
-        let mut initial = Vec::from_iter(get_initial_pixels(&mask, &mut marker));
+        let mut initial = Vec::from_iter(get_initial_pixels(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            Reconstruction::Dilation,
+        ));
         let mut expected = vec![(1, 1), (2, 1), (2, 2), (1, 2)];
 
         initial.sort();
@@ -210,9 +443,54 @@ mod tests {
         assert_eq!(initial, expected);
     }
 
+    #[test]
+    fn test_morph_reconstruction_asymmetric_neighbourhood() {
+        // Values only move to the right
+        let conn = Neighbourhood::new(vec![(1, 0)]);
+        let mut mask = _gen_same_value_image(5, 1, 9u8);
+        let marker = image::ImageBuffer::from_raw(5, 1, vec![0u8, 0, 5, 0, 0]).unwrap();
+
+        let mut result = marker.clone();
+        morph_reconstruction(&mut mask, &mut result, &conn).unwrap();
+        assert_eq!(result.as_raw(), &vec![0, 0, 5, 5, 5]);
+
+        let parallel =
+            morph_reconstruction_parallel(&mut mask, &mut marker.clone(), &conn, 2).unwrap();
+        assert_eq!(parallel, result);
+
+        let mut mask = _gen_same_value_image(5, 1, 0u8);
+        let mut result = image::ImageBuffer::from_raw(5, 1, vec![9u8, 9, 4, 9, 9]).unwrap();
+        morph_reconstruction_erosion(&mut mask, &mut result, &conn).unwrap();
+        assert_eq!(result.as_raw(), &vec![9, 9, 4, 4, 4]);
+    }
+
+    #[test]
+    fn test_morph_reconstruction_connectivity() {
+        let mut mask = _gen_example_img();
+
+        let mut marker = _gen_same_value_image(6, 6, 0);
+        marker.put_pixel(4, 4, Luma([1]));
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight).unwrap();
+
+        assert_eq!(marker, _gen_example_img());
+
+        let mut marker = _gen_same_value_image(6, 6, 0);
+        marker.put_pixel(4, 4, Luma([1]));
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Four).unwrap();
+
+        let mut expected = _gen_same_value_image(6, 6, 0);
+        for i in 3..5 {
+            for j in 3..5 {
+                expected.put_pixel(i, j, Luma([1]));
+            }
+        }
+
+        assert_eq!(marker, expected);
+    }
+
     #[test]
     fn test_propagation_phase() {
-        let mut mask = _gen_big_mask_img();
+        let mask = _gen_big_mask_img();
         let mut marker = _gen_big_marker_img();
 
         // did not use the get_initial_pixels function here because it does all the job
@@ -228,10 +506,9 @@ mod tests {
 
         iwp::propagate(
             &mut marker,
-            propagation_condition,
-            update_func,
+            &MRPropagator::dilation(&mask),
             &mut initial,
-            &mut mask,
+            ConnTypes::Eight,
         );
 
         assert_eq!(marker, _gen_expected_img());
@@ -241,184 +518,508 @@ mod tests {
     fn test_get_initial_pixels_parallel() {
         let num_threads = 8;
 
-        let img_mask = ImageReader::open("./tests/imgs/mr/mask.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mask = img_mask.to_luma8();
+        let (mask, mut marker) = _load_mr_imgs();
 
-        let img_marker = ImageReader::open("./tests/imgs/mr/marker.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut marker = img_marker.to_luma8();
-
-        let (mut marker_new, mut initial) =
-            get_initial_pixels_parallel(&mask, &mut marker, num_threads);
+        let (mut marker_new, initial) = get_initial_pixels_parallel(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            num_threads,
+            Reconstruction::Dilation,
+        )
+        .unwrap();
 
-        let mut marker_new_sections = parallel_img::arrange(&mut marker_new, num_threads);
+        let marker_new_sections = parallel_img::arrange(&mut marker_new, num_threads).unwrap();
+        let mask_sections = parallel_img::arrange(&mut mask.clone(), num_threads).unwrap();
 
-        let mut exp_sections = parallel_img::arrange(&mut marker, num_threads);
+        let mut exp_sections = parallel_img::arrange(&mut marker, num_threads).unwrap();
         let mut exp_queue = HashSet::new();
-        let mut count = 0;
-        for section in &mut exp_sections {
-            let marker_new_sec = marker_new_sections.get_mut(count).unwrap().slice.clone();
-            let exp_sec_initial = get_initial_pixels(&mask, &mut section.slice);
+        for (count, section) in exp_sections.iter_mut().enumerate() {
+            let marker_new_sec = &marker_new_sections[count].slice;
+            let mask_sec = &mask_sections[count].slice;
+            let exp_sec_initial = get_initial_pixels(
+                mask_sec,
+                &mut section.slice,
+                &ConnTypes::Eight.into(),
+                Reconstruction::Dilation,
+            );
             for val in exp_sec_initial {
                 exp_queue.insert(section.get_abs_pixel(val.0, val.1).coords);
             }
 
-            assert_eq!(section.slice, marker_new_sec);
+            let bounds = parallel_img::SectionBounds {
+                start: section.start,
+                width: section.width,
+                height: section.height,
+            };
+            exp_queue.extend(bounds.border_band(1));
 
-            count += 1;
+            assert_eq!(&section.slice, marker_new_sec);
         }
 
-        // WARNING: THIS IS DOING NOTHING -> .sort() returns () -> always true
-        assert_eq!(
-            Vec::from_iter(exp_queue).sort(),
-            Vec::from_iter(initial).sort()
-        );
+        let mut exp_queue = Vec::from_iter(exp_queue);
+        let mut initial = Vec::from_iter(initial);
+        exp_queue.sort();
+        initial.sort();
+
+        assert_eq!(exp_queue, initial);
     }
 
     #[test]
     fn test_propagation_phase_parallel() {
         let num_threads = 8;
 
-        let img_mask = ImageReader::open("./tests/imgs/mr/mask.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut mask = img_mask.to_luma8();
+        let (mask, mut marker) = _load_mr_imgs();
 
-        let img_marker = ImageReader::open("./tests/imgs/mr/marker.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut marker = img_marker.to_luma8();
+        let mut initial = get_initial_pixels(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            Reconstruction::Dilation,
+        );
 
-        // did not use the get_initial_pixels function here because it does all the job
-        let mut initial = get_initial_pixels(&mask, &mut marker);
+        let mut result = marker.clone();
+        let stats = iwp::propagate_parallel(
+            &mut result,
+            &MRPropagator::dilation(&mask),
+            &mut initial.clone(),
+            ConnTypes::Eight,
+            num_threads,
+        )
+        .unwrap();
+
+        // Every seed lands in exactly one section and every update queues
+        // one more pixel
+        let queued: usize = stats.sections.iter().map(|s| s.queue_len).sum();
+        assert_eq!(queued, initial.len());
+        assert_eq!(stats.pixels_processed(), queued as u64 + stats.updates());
 
-        let mut result = iwp::propagate_parallel(
+        iwp::propagate(
             &mut marker,
-            propagation_condition,
-            update_func,
+            &MRPropagator::dilation(&mask),
             &mut initial,
-            &mut mask,
-            num_threads,
+            ConnTypes::Eight,
         );
 
-        let mut result_sections = parallel_img::arrange(&mut result, num_threads);
-        let mut exp_sections = parallel_img::arrange(&mut marker, num_threads);
-        let mut mask_sections = parallel_img::arrange(&mut mask, num_threads);
+        assert_eq!(marker, result);
+    }
 
-        let mut count = 0;
-        for section in &mut exp_sections {
-            let result_sec = result_sections.get_mut(count).unwrap();
-            let result_sec_slice = result_sec.slice.clone();
-            let mut mask_slice = mask_sections.get_mut(count).unwrap().slice.clone();
+    #[test]
+    fn test_propagation_phase_parallel_disc() {
+        let num_threads = 8;
+        let disc = Neighbourhood::disc(2);
 
-            let mut exp_sec_initial = VecDeque::new();
+        let (mask, mut marker) = _load_mr_imgs();
 
-            for c in initial.clone() {
-                if is_pixel_in_section(c, section) {
-                    exp_sec_initial.push_back((c.0 - section.start.0, c.1 - section.start.1));
-                }
-            }
+        let mut initial = get_initial_pixels(&mask, &mut marker, &disc, Reconstruction::Dilation);
+
+        let mut result = marker.clone();
+        iwp::propagate_parallel(
+            &mut result,
+            &MRPropagator::dilation(&mask),
+            &mut initial.clone(),
+            &disc,
+            num_threads,
+        )
+        .unwrap();
+
+        iwp::propagate(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut initial,
+            &disc,
+        );
+
+        assert_eq!(marker, result);
+    }
+
+    #[test]
+    fn test_propagation_phase_parallel_exchange() {
+        let (mask, marker) = _load_mr_imgs();
+
+        for conn in [ConnTypes::Four.into(), Neighbourhood::disc(2)] {
+            let mut expected = marker.clone();
+            let mut initial =
+                get_initial_pixels(&mask, &mut expected, &conn, Reconstruction::Dilation);
+            let mut result = expected.clone();
+
+            iwp::propagate_parallel_exchange(
+                &mut result,
+                &MRPropagator::dilation(&mask),
+                &mut initial.clone(),
+                &conn,
+                7,
+            )
+            .unwrap();
 
             iwp::propagate(
-                &mut section.slice,
-                propagation_condition,
-                update_func,
-                &mut exp_sec_initial,
-                &mut mask_slice,
+                &mut expected,
+                &MRPropagator::dilation(&mask),
+                &mut initial,
+                &conn,
             );
 
-            assert_eq!(section.slice, result_sec_slice);
-
-            count += 1;
+            assert_eq!(expected, result);
         }
     }
 
     #[test]
-    fn test_propagation_phase_parallel_time() {
-        let img_mask = ImageReader::open("./tests/imgs/mr/50-percent-mask.jpg")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut mask = img_mask.to_luma8();
+    fn test_morph_reconstruction_parallel() {
+        let (mut mask, mut marker) = _load_mr_imgs();
 
-        let img_marker = ImageReader::open("./tests/imgs/mr/50-percent-marker.jpg")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut marker = img_marker.to_luma8();
+        let result =
+            morph_reconstruction_parallel(&mut mask, &mut marker.clone(), ConnTypes::Eight, 6)
+                .unwrap();
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight).unwrap();
+
+        assert_eq!(marker, result);
+    }
+
+    #[test]
+    fn test_morph_reconstruction_shared() {
+        let (mut mask, mut marker) = _load_mr_imgs();
+
+        let result =
+            morph_reconstruction_shared(&mut mask, &mut marker.clone(), ConnTypes::Four, 6)
+                .unwrap();
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Four).unwrap();
+
+        assert_eq!(marker, result);
+
+        // No sections to split the image into, so more threads than pixels
+        // are fine
+        let mut mask = image::ImageBuffer::from_raw(2, 1, vec![5u8, 5]).unwrap();
+        let marker = image::ImageBuffer::from_raw(2, 1, vec![5u8, 0]).unwrap();
+        let result =
+            morph_reconstruction_shared(&mut mask, &mut marker.clone(), ConnTypes::Four, 3)
+                .unwrap();
+        assert_eq!(result, mask);
+    }
+
+    #[test]
+    fn test_propagation_phase_parallel_time() {
+        let (mask, mut marker) = _load_mr_imgs();
 
         //print_image_by_row(&marker);
 
         let num_threads = 15;
-        let (mut marker_new, mut initial) =
-            get_initial_pixels_parallel(&mask, &mut marker, num_threads);
+        let (mut marker_new, mut initial) = get_initial_pixels_parallel(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            num_threads,
+            Reconstruction::Dilation,
+        )
+        .unwrap();
 
         //print_image_by_row(&markerr);
 
-        marker_new.save("marker_new.jpg").unwrap();
-
-        let result = iwp::propagate_parallel(
+        iwp::propagate_parallel(
             &mut marker_new,
-            propagation_condition,
-            update_func,
+            &MRPropagator::dilation(&mask),
             &mut initial,
-            &mut mask,
+            ConnTypes::Eight,
             num_threads,
-        );
-
-        result.save("result.jpg").unwrap();
+        )
+        .unwrap();
     }
 
     #[test]
     fn test_propagation_phase_parallel_2() {
         let now = Instant::now();
 
-        let img_mask = ImageReader::open("./tests/imgs/mr/50-percent-mask.jpg")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut mask = img_mask.to_luma8();
-
-        let img_marker = ImageReader::open("./tests/imgs/mr/50-percent-marker.jpg")
-            .unwrap()
-            .decode()
-            .unwrap();
-        let mut marker = img_marker.to_luma8();
+        let (mask, mut marker) = _load_mr_imgs();
 
         let num_threads = 12;
-        let (mut marker_new, mut initial) =
-            get_initial_pixels_parallel(&mask, &mut marker, num_threads);
+        let (mut marker_new, mut initial) = get_initial_pixels_parallel(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            num_threads,
+            Reconstruction::Dilation,
+        )
+        .unwrap();
 
-        let result = iwp::propagate_parallel(
+        iwp::propagate_parallel(
             &mut marker_new,
-            propagation_condition,
-            update_func,
+            &MRPropagator::dilation(&mask),
             &mut initial,
-            &mut mask,
+            ConnTypes::Eight,
             num_threads,
-        );
+        )
+        .unwrap();
 
         println!("parallel = {:?}", now.elapsed().as_secs_f32());
         let now_2 = Instant::now();
 
-        let mut initial = get_initial_pixels(&mask, &mut marker);
+        let mut initial = get_initial_pixels(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            Reconstruction::Dilation,
+        );
         iwp::propagate(
             &mut marker,
-            propagation_condition,
-            update_func,
+            &MRPropagator::dilation(&mask),
             &mut initial,
-            &mut mask,
+            ConnTypes::Eight,
         );
 
         println!("sequential = {:?}", now_2.elapsed().as_secs_f32());
 
-        assert_eq!(marker, result);
+        assert_eq!(marker, marker_new);
+    }
+
+    #[test]
+    fn test_propagate_monitored_cancel_and_resume() {
+        let (mask, mut marker) = _load_mr_imgs();
+        let mut queue = get_initial_pixels(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            Reconstruction::Dilation,
+        );
+
+        let mut expected = marker.clone();
+        iwp::propagate(
+            &mut expected,
+            &MRPropagator::dilation(&mask),
+            &mut queue.clone(),
+            ConnTypes::Eight,
+        );
+
+        let token = CancelToken::new();
+        let reports = Mutex::new(Vec::new());
+        let monitor = Monitor::new()
+            .interval(1000)
+            .cancel_token(token.clone())
+            .on_progress(|progress| {
+                reports.lock().unwrap().push(progress);
+                if progress.pixels_processed >= 5000 {
+                    token.cancel();
+                }
+            });
+
+        let err = iwp::propagate_monitored(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut queue,
+            ConnTypes::Eight,
+            &monitor,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.pixels_processed, 5000);
+        assert_eq!(reports.lock().unwrap().len(), 5);
+        assert!(!queue.is_empty());
+        assert_ne!(marker, expected);
+
+        iwp::propagate(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut queue,
+            ConnTypes::Eight,
+        );
+        assert_eq!(marker, expected);
+    }
+
+    #[test]
+    fn test_morph_reconstruction_parallel_monitored() {
+        let (mut mask, marker) = _load_mr_imgs();
+        let expected =
+            morph_reconstruction_parallel(&mut mask, &mut marker.clone(), ConnTypes::Eight, 4)
+                .unwrap();
+
+        // Cancelled after the first round
+        let token = CancelToken::new();
+        let reports = Mutex::new(Vec::new());
+        let monitor = Monitor::new()
+            .cancel_token(token.clone())
+            .on_progress(|progress| {
+                reports.lock().unwrap().push(progress);
+                token.cancel();
+            });
+
+        let mut partial = marker.clone();
+        let err = morph_reconstruction_parallel_monitored(
+            &mut mask,
+            &mut partial,
+            ConnTypes::Eight,
+            4,
+            &monitor,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Cancelled(_)));
+        assert_eq!(reports.lock().unwrap().len(), 1);
+        assert_ne!(partial, marker);
+        assert_ne!(partial, expected);
+
+        let resumed =
+            morph_reconstruction_parallel(&mut mask, &mut partial, ConnTypes::Eight, 4).unwrap();
+        assert_eq!(resumed, expected);
+    }
+
+    #[test]
+    fn test_propagate_parallel_exchange_cancel_and_resume() {
+        let num_threads = 4;
+        let (mask, mut marker) = _load_mr_imgs();
+        let mut queue = get_initial_pixels(
+            &mask,
+            &mut marker,
+            &ConnTypes::Eight.into(),
+            Reconstruction::Dilation,
+        );
+
+        let mut expected = marker.clone();
+        iwp::propagate(
+            &mut expected,
+            &MRPropagator::dilation(&mask),
+            &mut queue.clone(),
+            ConnTypes::Eight,
+        );
+
+        // Cancelled during the first round
+        let token = CancelToken::new();
+        token.cancel();
+        let monitor = Monitor::new().interval(1).cancel_token(token);
+        let err = iwp::propagate_parallel_exchange_monitored(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut queue,
+            ConnTypes::Eight,
+            num_threads,
+            &monitor,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            Error::Cancelled(Cancelled {
+                pixels_processed: 0
+            })
+        );
+        assert!(!queue.is_empty());
+
+        // Cancelled after the first round
+        let token = CancelToken::new();
+        let rounds = Mutex::new(0);
+        let monitor = Monitor::new().cancel_token(token.clone()).on_progress(|_| {
+            *rounds.lock().unwrap() += 1;
+            token.cancel();
+        });
+        let err = iwp::propagate_parallel_exchange_monitored(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut queue,
+            ConnTypes::Eight,
+            num_threads,
+            &monitor,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Cancelled(c) if c.pixels_processed > 0));
+        assert_eq!(*rounds.lock().unwrap(), 1);
+        assert!(!queue.is_empty());
+
+        iwp::propagate_parallel_exchange(
+            &mut marker,
+            &MRPropagator::dilation(&mask),
+            &mut queue,
+            ConnTypes::Eight,
+            num_threads,
+        )
+        .unwrap();
+        assert_eq!(marker, expected);
+    }
+
+    #[test]
+    fn test_morph_reconstruction_rejects_bad_input() {
+        let mut mask = _gen_same_value_image(4, 3, 5u8);
+        let mut marker = _gen_same_value_image(3, 4, 0u8);
+
+        assert_eq!(
+            morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight),
+            Err(Error::DimensionMismatch {
+                expected: (4, 3),
+                found: (3, 4)
+            })
+        );
+        assert_eq!(
+            morph_reconstruction(
+                &mut _gen_same_value_image(0, 3, 0u8),
+                &mut _gen_same_value_image(0, 3, 0u8),
+                ConnTypes::Eight
+            ),
+            Err(Error::EmptyImage)
+        );
+
+        let mut marker = _gen_same_value_image(4, 3, 0u8);
+        assert_eq!(
+            morph_reconstruction_parallel(&mut mask, &mut marker, ConnTypes::Eight, 0),
+            Err(Error::NoThreads)
+        );
+        assert_eq!(
+            morph_reconstruction_parallel(&mut mask, &mut marker, ConnTypes::Eight, 16),
+            Err(Error::TooManySections {
+                num_sections: 16,
+                width: 4,
+                height: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_morph_reconstruction_erosion_is_dual() {
+        let (mut mask, mut marker) = _load_mr_imgs();
+
+        let mut inv_mask = mask.clone();
+        image::imageops::invert(&mut inv_mask);
+        let mut inv_marker = marker.clone();
+        image::imageops::invert(&mut inv_marker);
+
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight).unwrap();
+        let parallel = morph_reconstruction_erosion_parallel(
+            &mut inv_mask,
+            &mut inv_marker.clone(),
+            ConnTypes::Eight,
+            6,
+        )
+        .unwrap();
+        morph_reconstruction_erosion(&mut inv_mask, &mut inv_marker, ConnTypes::Eight).unwrap();
+
+        assert_eq!(parallel, inv_marker);
+        image::imageops::invert(&mut inv_marker);
+        assert_eq!(inv_marker, marker);
+    }
+
+    #[test]
+    fn test_morph_reconstruction_depths() {
+        let (mut mask, mut marker) = _load_mr_imgs();
+
+        // Stretching u8 values over the u16 range keeps their order
+        let to_u16 = |img: &image::GrayImage| -> image::ImageBuffer<Luma<u16>, Vec<u16>> {
+            image::ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                Luma([img.get_pixel(x, y).0[0] as u16 * 257])
+            })
+        };
+        let to_f32 = |img: &image::GrayImage| -> image::ImageBuffer<Luma<f32>, Vec<f32>> {
+            image::ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                Luma([img.get_pixel(x, y).0[0] as f32 / 255.0])
+            })
+        };
+
+        let mut mask_16 = to_u16(&mask);
+        let mut marker_16 = to_u16(&marker);
+        let mut mask_f = to_f32(&mask);
+        let mut marker_f = to_f32(&marker);
+
+        morph_reconstruction(&mut mask, &mut marker, ConnTypes::Eight).unwrap();
+        morph_reconstruction(&mut mask_16, &mut marker_16, ConnTypes::Eight).unwrap();
+        let parallel_f =
+            morph_reconstruction_parallel(&mut mask_f, &mut marker_f.clone(), ConnTypes::Eight, 4)
+                .unwrap();
+        morph_reconstruction(&mut mask_f, &mut marker_f, ConnTypes::Eight).unwrap();
+
+        assert_eq!(marker_16, to_u16(&marker));
+        assert_eq!(marker_f, to_f32(&marker));
+        assert_eq!(parallel_f, marker_f);
     }
 }
@@ -1,3 +1,4 @@
+use crate::error::{self, Error, Result};
 use crate::PixelT;
 use image::{imageops, ImageBuffer, Luma, Primitive};
 
@@ -33,11 +34,65 @@ pub fn get_full_img<P: Primitive>(
     return img;
 }
 
-pub fn arrange<P: Primitive + 'static>(
-    img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
-    num_sections: u32,
-) -> Vec<ParallelSection<P>> {
-    let mut sections: Vec<ParallelSection<P>> = Vec::new();
+/// Position and size of a section inside the full image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectionBounds {
+    pub start: (u32, u32),
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SectionBounds {
+    pub fn contains(&self, coords: (u32, u32)) -> bool {
+        return (self.start.0 <= coords.0)
+            && (coords.0 < self.start.0 + self.width)
+            && (self.start.1 <= coords.1)
+            && (coords.1 < self.start.1 + self.height);
+    }
+
+    /// The bounds grown by `margin` pixels on every side, clipped to an image
+    /// of the given dimensions.
+    pub fn expand(&self, margin: u32, img_width: u32, img_height: u32) -> SectionBounds {
+        let x = self.start.0.saturating_sub(margin);
+        let y = self.start.1.saturating_sub(margin);
+        let end_x = std::cmp::min(self.start.0 + self.width + margin, img_width);
+        let end_y = std::cmp::min(self.start.1 + self.height + margin, img_height);
+
+        return SectionBounds {
+            start: (x, y),
+            width: end_x - x,
+            height: end_y - y,
+        };
+    }
+
+    /// Absolute coordinates of the pixels lying within `thickness` of the
+    /// bounds' edges.
+    pub fn border_band(&self, thickness: u32) -> Vec<(u32, u32)> {
+        let mut band = Vec::new();
+        for j in 0..self.height {
+            for i in 0..self.width {
+                if i < thickness
+                    || j < thickness
+                    || i + thickness >= self.width
+                    || j + thickness >= self.height
+                {
+                    band.push((self.start.0 + i, self.start.1 + j));
+                }
+            }
+        }
+
+        return band;
+    }
+}
+
+/// Splits a `width` x `height` image into `num_sections` rectangles laid out
+/// in rows, the last row holding the orphan sections when `num_sections` is
+/// not a perfect grid. Fails when `num_sections` is zero; sections may be
+/// empty when the image is smaller than the grid.
+pub fn layout(width: u32, height: u32, num_sections: u32) -> Result<Vec<SectionBounds>> {
+    error::check_num_threads(num_sections)?;
+
+    let mut sections = Vec::new();
     let columns = (num_sections as f32).sqrt().ceil() as u32;
     let full_rows = num_sections / columns;
     let orphans = num_sections % columns;
@@ -48,64 +103,88 @@ pub fn arrange<P: Primitive + 'static>(
         full_rows + 1
     };
 
-    let base_width = img.width() / columns;
-    let base_height = img.height() / aux;
+    let base_width = width / columns;
+    let base_height = height / aux;
 
-    let width_leftover = img.width() % columns;
-    let height_leftover = img.height() % aux;
+    let width_leftover = width % columns;
+    let height_leftover = height % aux;
 
     for y in 0..full_rows {
         for x in 0..columns {
-            let width = if x == columns - 1 {
+            let section_width = if x == columns - 1 {
                 base_width + width_leftover
             } else {
                 base_width
             };
 
-            let height = if orphans == 0 && y == full_rows - 1 {
+            let section_height = if orphans == 0 && y == full_rows - 1 {
                 base_height + height_leftover
             } else {
                 base_height
             };
 
-            sections.push(ParallelSection {
+            sections.push(SectionBounds {
                 start: (x * base_width, y * base_height),
-                width,
-                height,
-                slice: imageops::crop(img, x * base_width, y * base_height, width, height)
-                    .to_image(),
+                width: section_width,
+                height: section_height,
             });
         }
     }
 
-    if orphans > 0 {
-        let orphan_width = img.width() / orphans;
-        let orphan_width_leftover = img.width() % orphans;
+    if let Some(orphan_width) = width.checked_div(orphans) {
+        let orphan_width_leftover = width % orphans;
         let y = full_rows;
         for x in 0..orphans {
-            let width = if x == orphans - 1 {
+            let section_width = if x == orphans - 1 {
                 orphan_width + orphan_width_leftover
             } else {
                 orphan_width
             };
 
-            sections.push(ParallelSection {
+            sections.push(SectionBounds {
                 start: (x * orphan_width, y * base_height),
-                width,
+                width: section_width,
                 height: base_height + height_leftover,
-                slice: imageops::crop(
-                    img,
-                    x * orphan_width,
-                    y * base_height,
-                    width,
-                    base_height + height_leftover,
-                )
-                .to_image(),
             });
         }
     }
 
-    return sections;
+    return Ok(sections);
+}
+
+/// Crops `img` into the sections given by `layout`. Fails when the image is
+/// empty or too small to give every section at least one pixel.
+pub fn arrange<P: Primitive + 'static>(
+    img: &mut image::ImageBuffer<Luma<P>, Vec<P>>,
+    num_sections: u32,
+) -> Result<Vec<ParallelSection<P>>> {
+    error::check_not_empty(img)?;
+
+    let bounds = layout(img.width(), img.height(), num_sections)?;
+    if bounds.iter().any(|b| b.width == 0 || b.height == 0) {
+        return Err(Error::TooManySections {
+            num_sections,
+            width: img.width(),
+            height: img.height(),
+        });
+    }
+
+    return Ok(bounds
+        .iter()
+        .map(|bounds| ParallelSection {
+            start: bounds.start,
+            width: bounds.width,
+            height: bounds.height,
+            slice: imageops::crop(
+                img,
+                bounds.start.0,
+                bounds.start.1,
+                bounds.width,
+                bounds.height,
+            )
+            .to_image(),
+        })
+        .collect());
 }
 
 impl<P: Primitive> ParallelSection<P> {
@@ -127,19 +206,88 @@ impl<P: Primitive> ParallelSection<P> {
 mod tests {
     #![allow(unused_imports)]
     use crate::{
-        examples::_gen_seq_img,
+        examples::{_gen_same_value_image, _gen_seq_img},
         format::print_image_by_row,
         parallel_img::{self, *},
     };
 
+    #[test]
+    fn test_layout_covers_image() {
+        for num_sections in 1..10 {
+            let sections = parallel_img::layout(7, 5, num_sections).unwrap();
+            assert_eq!(sections.len(), num_sections as usize);
+
+            for i in 0..7 {
+                for j in 0..5 {
+                    let owners = sections.iter().filter(|s| s.contains((i, j))).count();
+                    assert_eq!(owners, 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_section_bounds_expand() {
+        let bounds = SectionBounds {
+            start: (2, 0),
+            width: 3,
+            height: 2,
+        };
+
+        let expected = SectionBounds {
+            start: (1, 0),
+            width: 5,
+            height: 3,
+        };
+
+        assert_eq!(bounds.expand(1, 6, 4), expected);
+    }
+
+    #[test]
+    fn test_section_bounds_border_band() {
+        let bounds = SectionBounds {
+            start: (1, 1),
+            width: 3,
+            height: 3,
+        };
+
+        let band = bounds.border_band(1);
+        assert_eq!(band.len(), 8);
+        assert!(!band.contains(&(2, 2)));
+        assert_eq!(bounds.border_band(2).len(), 9);
+    }
+
     #[test]
     fn test_parallel_img_assemble() {
         let mut base_img = _gen_seq_img();
 
-        let sections = parallel_img::arrange(&mut base_img, 4);
+        let sections = parallel_img::arrange(&mut base_img, 4).unwrap();
 
         let assembled_img =
             parallel_img::get_full_img(base_img.width(), base_img.height(), &sections);
         assert_eq!(base_img, assembled_img);
     }
+
+    #[test]
+    fn arrange_rejects_bad_input() {
+        let mut img = _gen_same_value_image(3, 2, 0u8);
+
+        assert_eq!(
+            parallel_img::arrange(&mut img, 0).unwrap_err(),
+            Error::NoThreads
+        );
+        assert_eq!(
+            parallel_img::arrange(&mut img, 7).unwrap_err(),
+            Error::TooManySections {
+                num_sections: 7,
+                width: 3,
+                height: 2
+            }
+        );
+        assert_eq!(
+            parallel_img::arrange(&mut _gen_same_value_image(0, 0, 0u8), 1).unwrap_err(),
+            Error::EmptyImage
+        );
+        assert_eq!(parallel_img::arrange(&mut img, 6).unwrap().len(), 6);
+    }
 }
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// One queue per worker. A worker takes items from the front of its own
+/// queue and, once it runs dry, steals the back half of the longest queue
+/// of another worker.
+///
+/// Items popped are still counted as pending until `done` is called, so
+/// `is_finished` only becomes true once no worker can produce more work.
+pub struct StealingQueues<T> {
+    queues: Vec<Mutex<VecDeque<T>>>,
+    pending: AtomicUsize,
+}
+
+impl<T> StealingQueues<T> {
+    pub fn new(num_workers: usize) -> Self {
+        StealingQueues {
+            queues: (0..num_workers)
+                .map(|_| Mutex::new(VecDeque::new()))
+                .collect(),
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn num_workers(&self) -> usize {
+        return self.queues.len();
+    }
+
+    pub fn push(&self, worker: usize, items: impl IntoIterator<Item = T>) {
+        let mut queue = self.queues[worker].lock().unwrap();
+        let before = queue.len();
+        queue.extend(items);
+        self.pending
+            .fetch_add(queue.len() - before, Ordering::SeqCst);
+    }
+
+    /// Takes up to `max` items for `worker`, stealing from the others if its
+    /// own queue is empty. Returns an empty batch when nothing could be found.
+    pub fn pop(&self, worker: usize, max: usize) -> Vec<T> {
+        {
+            let mut queue = self.queues[worker].lock().unwrap();
+            if !queue.is_empty() {
+                let count = std::cmp::min(max, queue.len());
+                return queue.drain(..count).collect();
+            }
+        }
+
+        let victim = (0..self.queues.len())
+            .filter(|idx| *idx != worker)
+            .max_by_key(|idx| self.queues[*idx].lock().unwrap().len());
+
+        if let Some(victim) = victim {
+            let mut stolen = {
+                let mut queue = self.queues[victim].lock().unwrap();
+                let keep = queue.len() / 2;
+                queue.split_off(keep)
+            };
+
+            let count = std::cmp::min(max, stolen.len());
+            let batch = stolen.drain(..count).collect();
+            if !stolen.is_empty() {
+                self.queues[worker].lock().unwrap().append(&mut stolen);
+            }
+
+            return batch;
+        }
+
+        return Vec::new();
+    }
+
+    /// Marks `count` popped items as processed.
+    pub fn done(&self, count: usize) {
+        self.pending.fetch_sub(count, Ordering::SeqCst);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.pending.load(Ordering::SeqCst) == 0;
+    }
+}
+
+/// Runs `task` once for every index in `0..num_tasks` on `num_threads`
+/// workers. Each worker starts with a contiguous share of the indices and,
+/// once done with it, steals the remaining ones of the busiest workers.
+pub fn run_tasks<T: Fn(usize) + Sync>(num_tasks: usize, num_threads: u32, task: T) {
+    let queues = StealingQueues::new(num_threads as usize);
+    let share = std::cmp::max(1, num_tasks.div_ceil(num_threads as usize));
+    for worker in 0..queues.num_workers() {
+        let start = std::cmp::min(worker * share, num_tasks);
+        let end = std::cmp::min(start + share, num_tasks);
+        queues.push(worker, start..end);
+    }
+
+    thread::scope(|s| {
+        for worker in 0..queues.num_workers() {
+            let queues = &queues;
+            let task = &task;
+
+            s.spawn(move || {
+                // Tasks never spawn new ones, so an empty pop means we are done
+                loop {
+                    let batch = queues.pop(worker, 1);
+                    if batch.is_empty() {
+                        break;
+                    }
+
+                    for idx in batch {
+                        task(idx);
+                    }
+                    queues.done(1);
+                }
+            });
+        }
+    });
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use crate::scheduler::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[test]
+    fn test_stealing_queues() {
+        let queues = StealingQueues::new(2);
+        queues.push(0, 0..10);
+
+        assert_eq!(queues.pop(0, 3), vec![0, 1, 2]);
+
+        let stolen = queues.pop(1, 2);
+        assert_eq!(stolen, vec![6, 7]);
+        assert_eq!(queues.pop(1, 10), vec![8, 9]);
+        assert_eq!(queues.pop(0, 10), vec![3, 4, 5]);
+        assert!(queues.pop(0, 10).is_empty());
+
+        assert!(!queues.is_finished());
+        queues.done(10);
+        assert!(queues.is_finished());
+    }
+
+    #[test]
+    fn test_run_tasks() {
+        let counters: Vec<AtomicU32> = (0..37).map(|_| AtomicU32::new(0)).collect();
+
+        run_tasks(counters.len(), 4, |idx| {
+            counters[idx].fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(counters.iter().all(|c| c.load(Ordering::SeqCst) == 1));
+    }
+}
@@ -1,6 +1,4 @@
-use image;
 use image::io::Reader as ImageReader;
-use imagepkg;
 
 #[test]
 fn test_distance_transform() {
@@ -12,7 +10,14 @@ fn test_distance_transform() {
 
     let mut bin_img = imagepkg::convert_to_binary(&img);
 
-    let res = imagepkg::dist_transform(&mut bin_img, imagepkg::DistTypes::Euclidean);
+    let res = imagepkg::dist_transform(
+        &mut bin_img,
+        imagepkg::DistTypes::Euclidean,
+        imagepkg::ConnTypes::Eight,
+    )
+    .unwrap();
 
-    res.save("./tests/imgs/dist_transform/result.png").unwrap();
+    imagepkg::dist_to_u8(&res, imagepkg::DistScaling::Clamp)
+        .save("./tests/imgs/dist_transform/result.png")
+        .unwrap();
 }
@@ -1,6 +1,4 @@
-use image;
 use image::io::Reader as ImageReader;
-use imagepkg;
 
 use crate::common::rmse_between_imgs;
 
@@ -27,7 +25,7 @@ fn test_morphological_reconstruction() {
     let dimensions = mask.dimensions();
     println!("dimensions: {:?}", dimensions);
 
-    imagepkg::morph_reconstruction(&mut mask, &mut marker);
+    imagepkg::morph_reconstruction(&mut mask, &mut marker, imagepkg::ConnTypes::Eight).unwrap();
 
     let expected = ImageReader::open("./tests/imgs/mr/result_matlab.png")
         .unwrap()